@@ -0,0 +1,291 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::url::import_url;
+use crate::Dependency;
+use crate::LexDependencies;
+use crate::Lexer;
+use crate::LoadModule;
+use crate::Mode;
+use crate::ModuleWarning;
+use crate::Range;
+use crate::ResolveModule;
+use crate::Warning;
+
+/// The conditions of an `@import`, an empty `layer` is an anonymous layer.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct Condition {
+    layer: Option<String>,
+    supports: Option<String>,
+    media: Option<String>,
+}
+
+impl Condition {
+    /// The wrapping at-rules, e.g. `@media print {\n`, and how many they are.
+    fn wrapper(&self) -> (String, usize) {
+        let mut wrapper = String::new();
+        let mut nesting = 0;
+        if let Some(media) = &self.media {
+            wrapper += &format!("@media {media} {{\n");
+            nesting += 1;
+        }
+        if let Some(supports) = &self.supports {
+            wrapper += &format!("@supports ({supports}) {{\n");
+            nesting += 1;
+        }
+        match self.layer.as_deref() {
+            Some("") => wrapper += "@layer {\n",
+            Some(layer) => wrapper += &format!("@layer {layer} {{\n"),
+            None => {}
+        }
+        if self.layer.is_some() {
+            nesting += 1;
+        }
+        (wrapper, nesting)
+    }
+}
+
+#[derive(Debug, Default)]
+struct BundleState {
+    // The paths of the modules being written
+    stack: Vec<String>,
+    // The conditions of the modules being written
+    conditions: Vec<Condition>,
+    imported: HashSet<(String, Vec<Condition>)>,
+    // The first `@charset` of the modules
+    charset: Option<String>,
+    // The `@import`s that are not inlined, they are hoisted to the top
+    hoisted: Vec<String>,
+    warnings: Vec<ModuleWarning>,
+}
+
+impl BundleState {
+    fn warn(&mut self, path: &str, range: &Range, message: String) {
+        self.warnings.push(ModuleWarning {
+            path: path.to_string(),
+            range: range.clone(),
+            message,
+        });
+    }
+}
+
+/// Inlines `@import`s recursively, the imported content is wrapped in
+/// `@media`, `@supports` and `@layer` according to the conditions of the `@import`.
+/// The `@import`s that can't be resolved, e.g. `@import "https://..."`, are hoisted
+/// to the top of the bundle with their conditions, after the first `@charset`.
+#[derive(Debug)]
+pub struct Bundler<R, L> {
+    resolver: R,
+    loader: L,
+}
+
+impl<R: ResolveModule, L: LoadModule> Bundler<R, L> {
+    pub fn new(resolver: R, loader: L) -> Self {
+        Self { resolver, loader }
+    }
+
+    /// Writes the module at `path` with its `@import`s inlined, an `@import`
+    /// of the same module with the same conditions is only inlined once.
+    pub fn bundle(
+        &mut self,
+        path: &str,
+        output: &mut impl fmt::Write,
+    ) -> Result<Vec<ModuleWarning>, fmt::Error> {
+        let mut state = BundleState::default();
+        let mut body = String::new();
+        match self.loader.load_module(path) {
+            Some(source) => self.write_module(path, &source, &mut state, &mut body)?,
+            None => state.warnings.push(ModuleWarning {
+                path: path.to_string(),
+                range: Range::new(0, 0),
+                message: format!("Can't load '{path}'"),
+            }),
+        }
+        if let Some(charset) = &state.charset {
+            writeln!(output, "{charset}")?;
+        }
+        for import in &state.hoisted {
+            writeln!(output, "{import}")?;
+        }
+        output.write_str(&body)?;
+        Ok(state.warnings)
+    }
+
+    fn write_module(
+        &mut self,
+        path: &str,
+        source: &str,
+        state: &mut BundleState,
+        output: &mut impl fmt::Write,
+    ) -> fmt::Result {
+        let mut imports = Vec::new();
+        let mut lexer = Lexer::new(source);
+        let mut visitor = LexDependencies::new(
+            |dependency| {
+                if let Dependency::Import { .. } = dependency {
+                    imports.push(dependency);
+                }
+            },
+            |warning: Warning| {
+                state.warnings.push(ModuleWarning {
+                    path: path.to_string(),
+                    range: warning.range().clone(),
+                    message: warning.to_string(),
+                })
+            },
+            Mode::Css,
+        );
+        lexer.lex(&mut visitor);
+
+        state.stack.push(path.to_string());
+        let mut index = 0;
+        if let Some(end) = charset_end(source) {
+            state
+                .charset
+                .get_or_insert_with(|| source[..end].to_string());
+            index = end;
+        }
+        for import in imports {
+            let Dependency::Import {
+                request,
+                range,
+                layer,
+                supports,
+                media,
+            } = import
+            else {
+                continue;
+            };
+            output.write_str(&source[index..range.start as usize])?;
+            index = range.end as usize;
+            let condition = Condition {
+                layer: layer.map(str::to_string),
+                supports: supports
+                    .filter(|supports| !supports.trim().is_empty())
+                    .map(str::to_string),
+                media: media
+                    .map(str::trim)
+                    .filter(|media| !media.is_empty())
+                    .map(str::to_string),
+            };
+            let Some(resolved) = self.resolver.resolve_module(path, request) else {
+                if !is_external(request) {
+                    state.warn(path, &range, format!("Can't resolve '{request}'"));
+                }
+                let statement = &source[range.start as usize..range.end as usize];
+                let import = match import_url(statement) {
+                    Some(url) if !state.conditions.is_empty() => {
+                        let url = &statement[url.start as usize..url.end as usize];
+                        hoisted_import(url, state.conditions.iter().chain([&condition]))
+                    }
+                    _ => statement.to_string(),
+                };
+                if !state.hoisted.contains(&import) {
+                    state.hoisted.push(import);
+                }
+                continue;
+            };
+            if state.stack.contains(&resolved) {
+                state.warn(path, &range, format!("Circular @import of '{resolved}'"));
+                continue;
+            }
+
+            let (wrapper, nesting) = condition.wrapper();
+            state.conditions.push(condition);
+            let key = (resolved.clone(), state.conditions.clone());
+            if !state.imported.insert(key) {
+                state.conditions.pop();
+                continue;
+            }
+            let Some(imported) = self.loader.load_module(&resolved) else {
+                state.warn(path, &range, format!("Can't load '{resolved}'"));
+                state.conditions.pop();
+                continue;
+            };
+            output.write_str(&wrapper)?;
+            let mut content = String::new();
+            self.write_module(&resolved, &imported, state, &mut content)?;
+            output.write_str(&content)?;
+            if nesting > 0 && !content.is_empty() && !content.ends_with('\n') {
+                output.write_char('\n')?;
+            }
+            for _ in 0..nesting {
+                output.write_str("}\n")?;
+            }
+            state.conditions.pop();
+        }
+        output.write_str(&source[index..])?;
+        state.stack.pop();
+        Ok(())
+    }
+}
+
+/// The end of the `@charset "...";` at the start of `source`.
+fn charset_end(source: &str) -> Option<usize> {
+    let rest = source.strip_prefix("@charset \"")?;
+    let end = rest.find("\";")?;
+    Some("@charset \"".len() + end + 2)
+}
+
+/// Whether `request` is an absolute URL, e.g. `https://...` or `//...`, that is
+/// intentionally not bundled.
+fn is_external(request: &str) -> bool {
+    if request.starts_with("//") {
+        return true;
+    }
+    let Some((scheme, _)) = request.split_once(':') else {
+        return false;
+    };
+    // Not a drive letter, e.g. `C:\\a.css`
+    scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// An `@import` of `url` with the conditions of all the `@import`s it's nested in.
+fn hoisted_import<'a>(url: &str, conditions: impl Iterator<Item = &'a Condition>) -> String {
+    let mut layers = Vec::new();
+    let mut supports = Vec::new();
+    let mut media: Vec<String> = Vec::new();
+    for condition in conditions {
+        if let Some(layer) = &condition.layer {
+            layers.push(layer.as_str());
+        }
+        if let Some(condition) = &condition.supports {
+            supports.push(format!("({condition})"));
+        }
+        if let Some(condition) = &condition.media {
+            let queries = condition.split(',').map(str::trim);
+            media = if media.is_empty() {
+                queries.map(str::to_string).collect()
+            } else {
+                media
+                    .iter()
+                    .flat_map(|outer| {
+                        queries
+                            .clone()
+                            .map(move |inner| format!("{outer} and {inner}"))
+                    })
+                    .collect()
+            };
+        }
+    }
+    let mut import = format!("@import {url}");
+    if !layers.is_empty() {
+        // An anonymous layer can't be named, so the whole layer is anonymous
+        if layers.contains(&"") {
+            import += " layer";
+        } else {
+            import += &format!(" layer({})", layers.join("."));
+        }
+    }
+    if !supports.is_empty() {
+        import += &format!(" supports({})", supports.join(" and "));
+    }
+    if !media.is_empty() {
+        import += &format!(" {}", media.join(", "));
+    }
+    import + ";"
+}
@@ -1,17 +1,22 @@
+use std::collections::HashSet;
 use std::fmt::Display;
 
 use smallvec::smallvec;
 use smallvec::SmallVec;
 
+use crate::lexer::is_ident;
 use crate::lexer::is_white_space;
 use crate::lexer::start_ident_sequence;
 use crate::lexer::Visitor;
+use crate::lexer::C_APOSTROPHE;
 use crate::lexer::C_ASTERISK;
 use crate::lexer::C_COLON;
 use crate::lexer::C_COMMA;
+use crate::lexer::C_FULL_STOP;
 use crate::lexer::C_HYPHEN_MINUS;
 use crate::lexer::C_LEFT_CURLY;
 use crate::lexer::C_LEFT_PARENTHESIS;
+use crate::lexer::C_QUOTATION_MARK;
 use crate::lexer::C_RIGHT_CURLY;
 use crate::lexer::C_RIGHT_PARENTHESIS;
 use crate::lexer::C_SEMICOLON;
@@ -69,6 +74,13 @@ impl ImportData<'_> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AtRulePrelude {
+    Media,
+    Supports,
+    Container,
+}
+
 #[derive(Debug)]
 enum ImportDataSupports<'s> {
     None,
@@ -132,6 +144,10 @@ impl BalancedStack {
         self.0.pop()
     }
 
+    pub fn is_only_mode_classes(&self) -> bool {
+        self.0.iter().all(|item| item.kind.is_mode_class())
+    }
+
     pub fn pop_mode_pseudo_class(&mut self, mode_data: &mut ModeData) {
         loop {
             if let Some(last) = self.0.last() {
@@ -208,10 +224,15 @@ enum BalancedItemKind {
     Layer,
     Supports,
     PaletteMix,
+    Counter,
+    ViewTransition,
+    Selector,
     LocalFn,
     GlobalFn,
     LocalClass,
     GlobalClass,
+    Deep,
+    Slotted,
     Other,
 }
 
@@ -224,6 +245,12 @@ impl BalancedItemKind {
             "layer(" => Self::Layer,
             "supports(" => Self::Supports,
             "palette-mix(" => Self::PaletteMix,
+            "counter(" | "counters(" => Self::Counter,
+            ":view-transition-group("
+            | ":view-transition-image-pair("
+            | ":view-transition-old("
+            | ":view-transition-new(" => Self::ViewTransition,
+            "selector(" => Self::Selector,
             ":local(" => Self::LocalFn,
             ":global(" => Self::GlobalFn,
             ":local" => Self::LocalClass,
@@ -249,6 +276,41 @@ impl BalancedItemKind {
     }
 }
 
+fn split_value_import(params: &str) -> Option<(&str, &str)> {
+    for (i, _) in params.rmatch_indices("from") {
+        let names = &params[..i];
+        let from = &params[i + 4..];
+        if !names.ends_with(is_white_space) || !from.starts_with(is_white_space) {
+            continue;
+        }
+        let from = from.trim_start_matches(is_white_space);
+        let is_string = from.len() >= 2
+            && (from.starts_with('"') && from.ends_with('"')
+                || from.starts_with('\'') && from.ends_with('\''));
+        let is_ident = !from.is_empty() && !from.contains(|c| !is_ident(c));
+        if is_string || is_ident {
+            let names = names.trim_matches(is_white_space);
+            return (!names.is_empty()).then_some((names, from));
+        }
+        return None;
+    }
+    None
+}
+
+fn is_symbol_usage(lexer: &Lexer, names: &HashSet<&str>, ident: &str) -> Option<bool> {
+    if names.is_empty() || !names.contains(ident) {
+        return Some(false);
+    }
+    Some(!is_property_name(lexer)?)
+}
+
+/// Whether the ident before `lexer` is a property name, i.e. followed by ':'.
+fn is_property_name(lexer: &Lexer) -> Option<bool> {
+    let mut lexer = lexer.clone();
+    lexer.consume_white_space_and_comments()?;
+    Some(lexer.cur()? == C_COLON)
+}
+
 fn with_vendor_prefixed_eq(left: &str, right: &str, at_rule: bool) -> bool {
     let left = if at_rule {
         if let Some(left) = left.strip_prefix('@') {
@@ -265,6 +327,72 @@ fn with_vendor_prefixed_eq(left: &str, right: &str, at_rule: bool) -> bool {
         || matches!(left.strip_prefix("-o-"), Some(left) if left.eq_ignore_ascii_case(right))
 }
 
+fn is_animation_property(ident: &str) -> bool {
+    ident.eq_ignore_ascii_case("animation")
+        || ident.eq_ignore_ascii_case("animation-name")
+        || with_vendor_prefixed_eq(ident, "animation", false)
+        || with_vendor_prefixed_eq(ident, "animation-name", false)
+}
+
+fn trim_end_white_space_and_comments(mut s: &str) -> &str {
+    loop {
+        s = s.trim_end_matches(is_white_space);
+        match s.strip_suffix("*/").and_then(|s| s.rfind("/*")) {
+            Some(start) => s = &s[..start],
+            None => return s,
+        }
+    }
+}
+
+/// The end of the last compound selector of `selector`, before its pseudo-classes
+/// and pseudo-elements, e.g. after '.b' of '.a .b:hover::before'.
+fn compound_selector_end(selector: &str) -> usize {
+    let bytes = selector.as_bytes();
+    let is_escaped =
+        |i: usize| bytes[..i].iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 1;
+    let mut end = bytes.len();
+    loop {
+        let mut i = end;
+        if i > 0 && bytes[i - 1] == b')' && !is_escaped(i - 1) {
+            let mut depth = 0;
+            loop {
+                if i == 0 {
+                    return end;
+                }
+                i -= 1;
+                match bytes[i] {
+                    b')' if !is_escaped(i) => depth += 1,
+                    b'(' if !is_escaped(i) => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let ident_end = i;
+        while i > 0 {
+            let b = bytes[i - 1];
+            if b >= 0x80 || is_ident(b as char) || is_escaped(i - 1) || b == b'\\' && is_escaped(i)
+            {
+                i -= 1;
+            } else {
+                break;
+            }
+        }
+        if i == ident_end || i == 0 || bytes[i - 1] != b':' || is_escaped(i - 1) {
+            return end;
+        }
+        i -= 1;
+        if i > 0 && bytes[i - 1] == b':' {
+            i -= 1;
+        }
+        end = i;
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Range {
     pub start: Pos,
@@ -283,6 +411,29 @@ pub enum Mode {
     Global,
     Pure,
     Css,
+    /// Selectors are scoped by an attribute, e.g. `[data-v-7ba5bd90]` of Vue,
+    /// at `Dependency::ScopeInsertion`, only keyframes are local. The part of a
+    /// selector from `:global` on isn't scoped, e.g. `.a :global(.b)` is scoped
+    /// as `.a[data-v] .b`, a selector starting with `:global` isn't scoped.
+    Scoped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScopeInsertion {
+    // The scope attribute is inserted at the end of the selector
+    Pending,
+    // The selector is scoped by ':deep()' or ':slotted()', or it's global
+    Done,
+    // Not the selectors of a style rule, e.g. the prelude of an at-rule
+    Skip,
+}
+
+/// Names that are global by default, but can be made local.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct LocalScope {
+    pub custom_media: bool,
+    pub custom_selector: bool,
+    pub layer: bool,
 }
 
 #[derive(Debug)]
@@ -315,10 +466,14 @@ impl ModeData<'_> {
         matches!(self.default, Mode::Pure)
     }
 
+    pub fn is_scoped_mode(&self) -> bool {
+        matches!(self.default, Mode::Scoped)
+    }
+
     pub fn is_current_local_mode(&self) -> bool {
         match self.current {
             Mode::Local | Mode::Pure => true,
-            Mode::Global => false,
+            Mode::Global | Mode::Scoped => false,
             Mode::Css => unreachable!(),
         }
     }
@@ -326,11 +481,19 @@ impl ModeData<'_> {
     pub fn is_property_local_mode(&self) -> bool {
         match self.property {
             Mode::Local | Mode::Pure => true,
-            Mode::Global => false,
+            Mode::Global | Mode::Scoped => false,
             Mode::Css => unreachable!(),
         }
     }
 
+    pub fn is_current_local_keyframes_mode(&self) -> bool {
+        self.is_current_local_mode() || matches!(self.current, Mode::Scoped)
+    }
+
+    pub fn is_property_local_keyframes_mode(&self) -> bool {
+        self.is_property_local_mode() || matches!(self.property, Mode::Scoped)
+    }
+
     pub fn default_mode(&self) -> Mode {
         self.default
     }
@@ -399,12 +562,14 @@ impl<'s> ComposesLocalClasses<'s> {
     }
 
     pub fn find_comma(&mut self, lexer: &Lexer<'s>) -> Option<()> {
-        if let SingleLocalClass::Single(range) = &self.is_single {
-            self.local_classes
-                .push(lexer.slice(range.start, range.end)?);
-            self.is_single = SingleLocalClass::Initial
-        } else {
-            self.is_single = SingleLocalClass::Invalid;
+        match &self.is_single {
+            SingleLocalClass::Single(range) => {
+                self.local_classes
+                    .push(lexer.slice(range.start, range.end)?);
+                self.is_single = SingleLocalClass::Initial
+            }
+            SingleLocalClass::AtKeyword => {}
+            _ => self.is_single = SingleLocalClass::Invalid,
         }
         Some(())
     }
@@ -609,6 +774,42 @@ impl ReservedValues for ListStyleReserved {
     fn reset(&mut self) {}
 }
 
+#[derive(Debug, Default)]
+struct CounterStyleSystemReserved;
+
+impl ReservedValues for CounterStyleSystemReserved {
+    fn check(&mut self, ident: &str) -> bool {
+        match ident {
+            // https://www.w3.org/TR/css-counter-styles-3/#counter-style-system
+            "cyclic" | "numeric" | "alphabetic" | "symbolic" | "additive" | "fixed" | "extends" => {
+                false
+            }
+            _ => ListStyleReserved.check(ident),
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[derive(Debug, Default)]
+struct CounterReserved;
+
+impl ReservedValues for CounterReserved {
+    fn check(&mut self, ident: &str) -> bool {
+        match ident {
+            // keywords values
+            "none"
+            // https://www.w3.org/TR/css-lists-3/#list-item-counter
+            | "list-item"
+            // global values
+            | "initial" | "inherit" | "unset" | "revert" | "revert-layer" => false,
+            _ => true,
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
 #[derive(Debug, Default)]
 struct FontPaletteReserved;
 
@@ -620,6 +821,186 @@ impl ReservedValues for FontPaletteReserved {
     fn reset(&mut self) {}
 }
 
+#[derive(Debug, Default)]
+struct ContainerReserved;
+
+impl ReservedValues for ContainerReserved {
+    fn check(&mut self, ident: &str) -> bool {
+        ![
+            // https://drafts.csswg.org/css-contain-3/#container-name
+            "none",
+            "and",
+            "not",
+            "or",
+            // container-type values of the `container` shorthand
+            "normal",
+            "size",
+            "inline-size",
+            "scroll-state",
+            // global values
+            "initial",
+            "inherit",
+            "unset",
+            "revert",
+            "revert-layer",
+        ]
+        .iter()
+        .any(|reserved| ident.eq_ignore_ascii_case(reserved))
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[derive(Debug, Default)]
+struct ViewTransitionNameReserved;
+
+impl ReservedValues for ViewTransitionNameReserved {
+    fn check(&mut self, ident: &str) -> bool {
+        match ident {
+            // keywords values
+            "none" | "auto" | "match-element"
+            // global values
+            | "initial" | "inherit" | "unset" | "revert" | "revert-layer" => false,
+            _ => true,
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[derive(Debug, Default)]
+struct ViewTransitionClassReserved;
+
+impl ReservedValues for ViewTransitionClassReserved {
+    fn check(&mut self, ident: &str) -> bool {
+        match ident {
+            // keywords values
+            "none"
+            // global values
+            | "initial" | "inherit" | "unset" | "revert" | "revert-layer" => false,
+            _ => true,
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Whether `ident` is a keyword of a property that takes local names, e.g.
+/// `ease` of `animation` or `decimal` of `list-style`.
+pub(crate) fn is_reserved_keyword(ident: &str) -> bool {
+    !AnimationReserved::default().check(ident)
+        || !CounterStyleSystemReserved.check(ident)
+        || !CounterReserved.check(ident)
+        || !ContainerReserved.check(ident)
+        || !ViewTransitionNameReserved.check(ident)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GridProperty {
+    // grid-template-areas, grid-template-rows, grid-template-columns, grid-template, grid
+    Template,
+    // Inside `[...]` of the template properties
+    TemplateLineNames,
+    // grid-area, grid-row, grid-column, grid-row-start, grid-row-end, grid-column-start, grid-column-end
+    Placement,
+}
+
+impl GridProperty {
+    pub fn new(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("grid-template-areas")
+            || name.eq_ignore_ascii_case("grid-template-rows")
+            || name.eq_ignore_ascii_case("grid-template-columns")
+            || name.eq_ignore_ascii_case("grid-template")
+            || name.eq_ignore_ascii_case("grid")
+        {
+            Some(Self::Template)
+        } else if name.eq_ignore_ascii_case("grid-area")
+            || name.eq_ignore_ascii_case("grid-row")
+            || name.eq_ignore_ascii_case("grid-column")
+            || name.eq_ignore_ascii_case("grid-row-start")
+            || name.eq_ignore_ascii_case("grid-row-end")
+            || name.eq_ignore_ascii_case("grid-column-start")
+            || name.eq_ignore_ascii_case("grid-column-end")
+        {
+            Some(Self::Placement)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DashedIdentProperty {
+    // anchor-name
+    AnchorDecl,
+    // position-anchor, anchor-scope
+    Anchor,
+    // position-try-fallbacks, position-try
+    PositionTry,
+    // scroll-timeline-name, view-timeline-name, scroll-timeline, view-timeline
+    TimelineDecl,
+    // animation-timeline, timeline-scope
+    Timeline,
+}
+
+impl DashedIdentProperty {
+    pub fn new(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("anchor-name") {
+            Some(Self::AnchorDecl)
+        } else if name.eq_ignore_ascii_case("position-anchor")
+            || name.eq_ignore_ascii_case("anchor-scope")
+        {
+            Some(Self::Anchor)
+        } else if name.eq_ignore_ascii_case("position-try-fallbacks")
+            || name.eq_ignore_ascii_case("position-try")
+        {
+            Some(Self::PositionTry)
+        } else if name.eq_ignore_ascii_case("scroll-timeline-name")
+            || name.eq_ignore_ascii_case("view-timeline-name")
+            || name.eq_ignore_ascii_case("scroll-timeline")
+            || name.eq_ignore_ascii_case("view-timeline")
+        {
+            Some(Self::TimelineDecl)
+        } else if name.eq_ignore_ascii_case("animation-timeline")
+            || name.eq_ignore_ascii_case("timeline-scope")
+        {
+            Some(Self::Timeline)
+        } else {
+            None
+        }
+    }
+
+    pub fn allow_from(&self) -> bool {
+        matches!(self, Self::Timeline)
+    }
+
+    // `from` is only available for the references that `allow_from()`
+    pub fn dependency<'s>(
+        &self,
+        name: &'s str,
+        range: Range,
+        from: Option<&'s str>,
+    ) -> Dependency<'s> {
+        match self {
+            Self::AnchorDecl => Dependency::LocalAnchorDecl { name, range },
+            Self::Anchor => Dependency::LocalAnchor { name, range },
+            Self::PositionTry => Dependency::LocalPositionTry { name, range },
+            Self::TimelineDecl => Dependency::LocalTimelineDecl { name, range },
+            Self::Timeline => Dependency::LocalTimeline { name, range, from },
+        }
+    }
+}
+
+fn is_grid_placement_reserved(ident: &str) -> bool {
+    matches!(
+        ident.to_ascii_lowercase().as_str(),
+        // keywords values
+        "auto" | "span" |
+        // global values
+        "initial" | "inherit" | "unset" | "revert" | "revert-layer"
+    )
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Dependency<'s> {
     Url {
@@ -677,6 +1058,10 @@ pub enum Dependency<'s> {
         name: &'s str,
         range: Range,
     },
+    LocalCounter {
+        name: &'s str,
+        range: Range,
+    },
     LocalFontPalette {
         name: &'s str,
         range: Range,
@@ -685,6 +1070,63 @@ pub enum Dependency<'s> {
         name: &'s str,
         range: Range,
     },
+    LocalGridArea {
+        name: &'s str,
+        range: Range,
+    },
+    LocalGridLine {
+        name: &'s str,
+        range: Range,
+    },
+    LocalContainer {
+        name: &'s str,
+        range: Range,
+    },
+    LocalContainerDecl {
+        name: &'s str,
+        range: Range,
+    },
+    LocalAnchor {
+        name: &'s str,
+        range: Range,
+    },
+    LocalAnchorDecl {
+        name: &'s str,
+        range: Range,
+    },
+    LocalPositionTry {
+        name: &'s str,
+        range: Range,
+    },
+    LocalPositionTryDecl {
+        name: &'s str,
+        range: Range,
+    },
+    LocalTimeline {
+        name: &'s str,
+        range: Range,
+        from: Option<&'s str>,
+    },
+    LocalTimelineDecl {
+        name: &'s str,
+        range: Range,
+    },
+    LocalViewTransitionName {
+        name: &'s str,
+        range: Range,
+    },
+    LocalViewTransitionNameDecl {
+        name: &'s str,
+        range: Range,
+    },
+    LocalViewTransitionClass {
+        name: &'s str,
+        range: Range,
+    },
+    LocalViewTransitionClassDecl {
+        name: &'s str,
+        range: Range,
+    },
     Composes {
         local_classes: SmallVec<[&'s str; 2]>,
         names: SmallVec<[&'s str; 2]>,
@@ -698,10 +1140,230 @@ pub enum Dependency<'s> {
         prop: &'s str,
         value: &'s str,
     },
+    ICSSImportUsage {
+        name: &'s str,
+        range: Range,
+    },
     ICSSExportValue {
         prop: &'s str,
         value: &'s str,
     },
+    ValueDecl {
+        name: &'s str,
+        value: &'s str,
+    },
+    ValueImport {
+        name: &'s str,
+        alias: &'s str,
+        from: &'s str,
+    },
+    ValueUsage {
+        name: &'s str,
+        range: Range,
+    },
+    CustomMediaDecl {
+        name: &'s str,
+        range: Range,
+        local: bool,
+    },
+    CustomMedia {
+        name: &'s str,
+        range: Range,
+        local: bool,
+    },
+    CustomSelectorDecl {
+        name: &'s str,
+        range: Range,
+        local: bool,
+    },
+    CustomSelector {
+        name: &'s str,
+        range: Range,
+        local: bool,
+    },
+    LayerName {
+        name: &'s str,
+        range: Range,
+        local: bool,
+    },
+    /// A top-level rule or at-rule with a block, from its prelude to its '}',
+    /// only emitted with `LexDependencies::with_rule_boundaries`.
+    Rule {
+        range: Range,
+    },
+    /// Where the scope attribute of `Mode::Scoped` is inserted, `range` is empty.
+    /// `slotted` is for the selectors of ':slotted()'.
+    ScopeInsertion {
+        range: Range,
+        slotted: bool,
+    },
+}
+
+impl<'s> Dependency<'s> {
+    pub fn range(&self) -> Option<&Range> {
+        match self {
+            Dependency::Url { range, .. }
+            | Dependency::Import { range, .. }
+            | Dependency::Replace { range, .. }
+            | Dependency::LocalClass { range, .. }
+            | Dependency::LocalId { range, .. }
+            | Dependency::LocalVar { range, .. }
+            | Dependency::LocalVarDecl { range, .. }
+            | Dependency::LocalPropertyDecl { range, .. }
+            | Dependency::LocalKeyframes { range, .. }
+            | Dependency::LocalKeyframesDecl { range, .. }
+            | Dependency::LocalCounterStyle { range, .. }
+            | Dependency::LocalCounterStyleDecl { range, .. }
+            | Dependency::LocalCounter { range, .. }
+            | Dependency::LocalFontPalette { range, .. }
+            | Dependency::LocalFontPaletteDecl { range, .. }
+            | Dependency::LocalGridArea { range, .. }
+            | Dependency::LocalGridLine { range, .. }
+            | Dependency::LocalContainer { range, .. }
+            | Dependency::LocalContainerDecl { range, .. }
+            | Dependency::LocalAnchor { range, .. }
+            | Dependency::LocalAnchorDecl { range, .. }
+            | Dependency::LocalPositionTry { range, .. }
+            | Dependency::LocalPositionTryDecl { range, .. }
+            | Dependency::LocalTimeline { range, .. }
+            | Dependency::LocalTimelineDecl { range, .. }
+            | Dependency::LocalViewTransitionName { range, .. }
+            | Dependency::LocalViewTransitionNameDecl { range, .. }
+            | Dependency::LocalViewTransitionClass { range, .. }
+            | Dependency::LocalViewTransitionClassDecl { range, .. }
+            | Dependency::Composes { range, .. }
+            | Dependency::ICSSImportUsage { range, .. }
+            | Dependency::ValueUsage { range, .. }
+            | Dependency::CustomMediaDecl { range, .. }
+            | Dependency::CustomMedia { range, .. }
+            | Dependency::CustomSelectorDecl { range, .. }
+            | Dependency::CustomSelector { range, .. }
+            | Dependency::LayerName { range, .. }
+            | Dependency::Rule { range }
+            | Dependency::ScopeInsertion { range, .. } => Some(range),
+            Dependency::ICSSImportFrom { .. }
+            | Dependency::ICSSImportValue { .. }
+            | Dependency::ICSSExportValue { .. }
+            | Dependency::ValueDecl { .. }
+            | Dependency::ValueImport { .. } => None,
+        }
+    }
+
+    /// The local name this dependency refers to, names of '.class' and '#id' are without the prefix.
+    pub fn local_name(&self) -> Option<LocalName<'s>> {
+        let (kind, name, range, from) = match self {
+            Dependency::LocalClass { name, range, .. } => {
+                (LocalKind::Class, &name[1..], range, None)
+            }
+            Dependency::LocalId { name, range, .. } => (LocalKind::Id, &name[1..], range, None),
+            Dependency::LocalVar { name, range, from } => (LocalKind::Var, *name, range, *from),
+            Dependency::LocalVarDecl { name, range }
+            | Dependency::LocalPropertyDecl { name, range } => (LocalKind::Var, *name, range, None),
+            Dependency::LocalKeyframes { name, range }
+            | Dependency::LocalKeyframesDecl { name, range } => {
+                (LocalKind::Keyframes, *name, range, None)
+            }
+            Dependency::LocalCounterStyle { name, range }
+            | Dependency::LocalCounterStyleDecl { name, range } => {
+                (LocalKind::CounterStyle, *name, range, None)
+            }
+            Dependency::LocalCounter { name, range } => (LocalKind::Counter, *name, range, None),
+            Dependency::LocalFontPalette { name, range }
+            | Dependency::LocalFontPaletteDecl { name, range } => {
+                (LocalKind::FontPalette, *name, range, None)
+            }
+            Dependency::LocalGridArea { name, range }
+            | Dependency::LocalGridLine { name, range } => (LocalKind::Grid, *name, range, None),
+            Dependency::LocalContainer { name, range }
+            | Dependency::LocalContainerDecl { name, range } => {
+                (LocalKind::Container, *name, range, None)
+            }
+            Dependency::LocalAnchor { name, range }
+            | Dependency::LocalAnchorDecl { name, range } => {
+                (LocalKind::Anchor, *name, range, None)
+            }
+            Dependency::LocalPositionTry { name, range }
+            | Dependency::LocalPositionTryDecl { name, range } => {
+                (LocalKind::PositionTry, *name, range, None)
+            }
+            Dependency::LocalTimeline { name, range, from } => {
+                (LocalKind::Timeline, *name, range, *from)
+            }
+            Dependency::LocalTimelineDecl { name, range } => {
+                (LocalKind::Timeline, *name, range, None)
+            }
+            Dependency::LocalViewTransitionName { name, range }
+            | Dependency::LocalViewTransitionNameDecl { name, range } => {
+                (LocalKind::ViewTransitionName, *name, range, None)
+            }
+            Dependency::LocalViewTransitionClass { name, range }
+            | Dependency::LocalViewTransitionClassDecl { name, range } => {
+                (LocalKind::ViewTransitionClass, *name, range, None)
+            }
+            Dependency::CustomMedia {
+                name,
+                range,
+                local: true,
+            }
+            | Dependency::CustomMediaDecl {
+                name,
+                range,
+                local: true,
+            } => (LocalKind::CustomMedia, *name, range, None),
+            Dependency::CustomSelector {
+                name,
+                range,
+                local: true,
+            }
+            | Dependency::CustomSelectorDecl {
+                name,
+                range,
+                local: true,
+            } => (LocalKind::CustomSelector, *name, range, None),
+            Dependency::LayerName {
+                name,
+                range,
+                local: true,
+            } => (LocalKind::Layer, *name, range, None),
+            _ => return None,
+        };
+        Some(LocalName {
+            kind,
+            name,
+            range: range.clone(),
+            from,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LocalKind {
+    Class,
+    Id,
+    Var,
+    Keyframes,
+    CounterStyle,
+    Counter,
+    FontPalette,
+    Grid,
+    Container,
+    Anchor,
+    PositionTry,
+    Timeline,
+    ViewTransitionName,
+    ViewTransitionClass,
+    CustomMedia,
+    CustomSelector,
+    Layer,
+}
+
+/// A name that should be renamed, `range` ends with `name`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct LocalName<'s> {
+    pub kind: LocalKind,
+    pub name: &'s str,
+    pub range: Range,
+    pub from: Option<&'s str>,
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -798,7 +1460,26 @@ pub struct LexDependencies<'s, D, W> {
     is_next_rule_prelude: bool,
     in_animation_property: Option<InProperty<AnimationReserved>>,
     in_list_style_property: Option<InProperty<ListStyleReserved>>,
+    in_counter_style_system_property: Option<InProperty<CounterStyleSystemReserved>>,
+    in_counter_property: Option<InProperty<CounterReserved>>,
+    in_counter_style_block: bool,
     in_font_palette_property: Option<InProperty<FontPaletteReserved>>,
+    in_container_property: Option<InProperty<ContainerReserved>>,
+    in_view_transition_name_property: Option<InProperty<ViewTransitionNameReserved>>,
+    in_view_transition_class_property: Option<InProperty<ViewTransitionClassReserved>>,
+    in_grid_property: Option<GridProperty>,
+    in_dashed_ident_property: Option<DashedIdentProperty>,
+    value_names: HashSet<&'s str>,
+    icss_import_aliases: HashSet<&'s str>,
+    in_at_rule_prelude: Option<AtRulePrelude>,
+    local_scope: LocalScope,
+    rule_boundaries: bool,
+    rule_nesting_level: u32,
+    // The end of the last top-level rule or statement
+    rule_start: Pos,
+    scope_insertion: ScopeInsertion,
+    // The rule nesting level of '@keyframes', there are no selectors in its block
+    in_keyframes_block: Option<u32>,
     handle_dependency: D,
     handle_warning: W,
 }
@@ -818,17 +1499,74 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
             is_next_rule_prelude: true,
             in_animation_property: None,
             in_list_style_property: None,
+            in_counter_style_system_property: None,
+            in_counter_property: None,
+            in_counter_style_block: false,
             in_font_palette_property: None,
+            in_container_property: None,
+            in_view_transition_name_property: None,
+            in_view_transition_class_property: None,
+            in_grid_property: None,
+            in_dashed_ident_property: None,
+            value_names: HashSet::new(),
+            icss_import_aliases: HashSet::new(),
+            in_at_rule_prelude: None,
+            local_scope: LocalScope::default(),
+            rule_boundaries: false,
+            rule_nesting_level: 0,
+            rule_start: 0,
+            scope_insertion: ScopeInsertion::Pending,
+            in_keyframes_block: None,
             handle_dependency,
             handle_warning,
         }
     }
 
-    fn is_next_nested_syntax(&self, lexer: &mut Lexer) -> Option<bool> {
-        lexer.consume_white_space_and_comments()?;
-        let c = lexer.cur()?;
-        if c == C_RIGHT_CURLY {
-            return Some(false);
+    pub fn with_local_scope(mut self, local_scope: LocalScope) -> Self {
+        self.local_scope = local_scope;
+        self
+    }
+
+    /// Emits a `Dependency::Rule` after the dependencies of every top-level rule.
+    pub fn with_rule_boundaries(mut self, rule_boundaries: bool) -> Self {
+        self.rule_boundaries = rule_boundaries;
+        self
+    }
+
+    fn handle_rule_end(&mut self, lexer: &Lexer<'s>, end: Pos) -> Option<()> {
+        if self.rule_nesting_level == 0 {
+            return Some(());
+        }
+        self.rule_nesting_level -= 1;
+        if self.rule_nesting_level > 0 {
+            return Some(());
+        }
+        if self.rule_boundaries {
+            let mut rule = Lexer::new(lexer.slice(self.rule_start, end)?);
+            rule.consume();
+            rule.consume_white_space_and_comments()?;
+            let start = self.rule_start + rule.cur_pos()?;
+            self.handle_dependency.handle_dependency(Dependency::Rule {
+                range: Range::new(start, end),
+            });
+        }
+        self.rule_start = end;
+        Some(())
+    }
+
+    fn is_local_scope(&self, enabled: bool) -> bool {
+        enabled
+            && self
+                .mode_data
+                .as_ref()
+                .is_some_and(|mode_data| mode_data.is_current_local_mode())
+    }
+
+    fn is_next_nested_syntax(&self, lexer: &mut Lexer) -> Option<bool> {
+        lexer.consume_white_space_and_comments()?;
+        let c = lexer.cur()?;
+        if c == C_RIGHT_CURLY {
+            return Some(false);
         }
         // If what follows is a property, then it's not a nested selector
         // This is not strictly correct, but it's good enough for our purposes
@@ -863,6 +1601,25 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
         self.in_list_style_property = None;
     }
 
+    fn enter_counter_style_system_property(&mut self) {
+        self.in_counter_style_system_property = Some(InProperty::new(
+            CounterStyleSystemReserved,
+            self.balanced.len(),
+        ));
+    }
+
+    fn exit_counter_style_system_property(&mut self) {
+        self.in_counter_style_system_property = None;
+    }
+
+    fn enter_counter_property(&mut self) {
+        self.in_counter_property = Some(InProperty::new(CounterReserved, self.balanced.len()));
+    }
+
+    fn exit_counter_property(&mut self) {
+        self.in_counter_property = None;
+    }
+
     fn enter_font_palette_property(&mut self) {
         self.in_font_palette_property =
             Some(InProperty::new(FontPaletteReserved, self.balanced.len()));
@@ -872,6 +1629,52 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
         self.in_font_palette_property = None;
     }
 
+    fn enter_container_property(&mut self) {
+        self.in_container_property = Some(InProperty::new(ContainerReserved, self.balanced.len()));
+    }
+
+    fn exit_container_property(&mut self) {
+        self.in_container_property = None;
+    }
+
+    fn enter_view_transition_name_property(&mut self) {
+        self.in_view_transition_name_property = Some(InProperty::new(
+            ViewTransitionNameReserved,
+            self.balanced.len(),
+        ));
+    }
+
+    fn exit_view_transition_name_property(&mut self) {
+        self.in_view_transition_name_property = None;
+    }
+
+    fn enter_view_transition_class_property(&mut self) {
+        self.in_view_transition_class_property = Some(InProperty::new(
+            ViewTransitionClassReserved,
+            self.balanced.len(),
+        ));
+    }
+
+    fn exit_view_transition_class_property(&mut self) {
+        self.in_view_transition_class_property = None;
+    }
+
+    fn enter_grid_property(&mut self, grid: GridProperty) {
+        self.in_grid_property = Some(grid);
+    }
+
+    fn exit_grid_property(&mut self) {
+        self.in_grid_property = None;
+    }
+
+    fn enter_dashed_ident_property(&mut self, property: DashedIdentProperty) {
+        self.in_dashed_ident_property = Some(property);
+    }
+
+    fn exit_dashed_ident_property(&mut self) {
+        self.in_dashed_ident_property = None;
+    }
+
     fn back_white_space_and_comments_distance(&self, lexer: &Lexer<'s>, end: Pos) -> Option<Pos> {
         let mut lexer = lexer.clone().turn_back(end);
         lexer.consume();
@@ -979,11 +1782,13 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
                 lexer.consume();
                 lexer.consume_white_space_and_comments()?;
             }
+            let prop = lexer
+                .slice(prop_start, prop_end)?
+                .trim_end_matches(is_white_space);
+            self.icss_import_aliases.insert(prop);
             self.handle_dependency
                 .handle_dependency(Dependency::ICSSImportValue {
-                    prop: lexer
-                        .slice(prop_start, prop_end)?
-                        .trim_end_matches(is_white_space),
+                    prop,
                     value: lexer
                         .slice(value_start, value_end)?
                         .trim_end_matches(is_white_space),
@@ -1059,6 +1864,7 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
                         .slice(value_start, value_end)?
                         .trim_end_matches(is_white_space),
                 });
+            self.lex_icss_import_usages(lexer, value_start, value_end)?;
         }
         lexer.consume();
         Some(())
@@ -1077,8 +1883,20 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
             return Some(());
         }
         lexer.consume_ident_sequence()?;
-        let name_start = start + 2;
         let end = lexer.cur_pos()?;
+        self.lex_local_dashed_ident_from(lexer, start, end, |name, range, from| {
+            Dependency::LocalVar { name, range, from }
+        })
+    }
+
+    fn lex_local_dashed_ident_from(
+        &mut self,
+        lexer: &mut Lexer<'s>,
+        start: Pos,
+        end: Pos,
+        local_dependency: impl FnOnce(&'s str, Range, Option<&'s str>) -> Dependency<'s>,
+    ) -> Option<()> {
+        let name_start = start + 2;
         lexer.consume_white_space_and_comments()?;
         let from_start = lexer.cur_pos()?;
         let from = if matches!(lexer.slice(from_start, from_start + 4), Some("from")) {
@@ -1107,12 +1925,11 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
         } else {
             None
         };
-        self.handle_dependency
-            .handle_dependency(Dependency::LocalVar {
-                name: lexer.slice(name_start, end)?,
-                range: Range::new(start, end),
-                from,
-            });
+        self.handle_dependency.handle_dependency(local_dependency(
+            lexer.slice(name_start, end)?,
+            Range::new(start, end),
+            from,
+        ));
         Some(())
     }
 
@@ -1170,6 +1987,107 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
         Some(())
     }
 
+    fn lex_layer_names(&mut self, lexer: &mut Lexer<'s>) -> Option<()> {
+        let local = self.is_local_scope(self.local_scope.layer);
+        loop {
+            lexer.consume_white_space_and_comments()?;
+            let c = lexer.cur()?;
+            if c == C_COMMA || c == C_FULL_STOP {
+                lexer.consume();
+                continue;
+            }
+            if !start_ident_sequence(c, lexer.peek()?, lexer.peek2()?) {
+                return Some(());
+            }
+            let start = lexer.cur_pos()?;
+            lexer.consume_ident_sequence()?;
+            let end = lexer.cur_pos()?;
+            self.handle_dependency
+                .handle_dependency(Dependency::LayerName {
+                    name: lexer.slice(start, end)?,
+                    range: Range::new(start, end),
+                    local,
+                });
+        }
+    }
+
+    fn handle_import_layer_names(&mut self, value: &'s str, start: Pos) {
+        let local = self.is_local_scope(self.local_scope.layer);
+        let mut segment_start = 0;
+        for (i, c) in value
+            .char_indices()
+            .chain(std::iter::once((value.len(), C_FULL_STOP)))
+        {
+            if c != C_FULL_STOP {
+                continue;
+            }
+            let segment = &value[segment_start..i];
+            let name = segment.trim();
+            if !name.is_empty() {
+                let name_start =
+                    start + (segment_start + segment.len() - segment.trim_start().len()) as u32;
+                self.handle_dependency
+                    .handle_dependency(Dependency::LayerName {
+                        name,
+                        range: Range::new(name_start, name_start + name.len() as u32),
+                        local,
+                    });
+            }
+            segment_start = i + 1;
+        }
+    }
+
+    fn lex_custom_media_decl(&mut self, lexer: &mut Lexer<'s>) -> Option<()> {
+        lexer.consume_white_space_and_comments()?;
+        let start = lexer.cur_pos()?;
+        if lexer.cur()? != C_HYPHEN_MINUS || lexer.peek()? != C_HYPHEN_MINUS {
+            self.handle_warning.handle_warning(Warning {
+                range: Range::new(start, lexer.peek2_pos()?),
+                kind: WarningKind::Unexpected {
+                    message: "Expected starts with '--' during parsing of '@custom-media'",
+                },
+            });
+            return Some(());
+        }
+        lexer.consume_ident_sequence()?;
+        let end = lexer.cur_pos()?;
+        self.handle_dependency
+            .handle_dependency(Dependency::CustomMediaDecl {
+                name: lexer.slice(start + 2, end)?,
+                range: Range::new(start, end),
+                local: self.is_local_scope(self.local_scope.custom_media),
+            });
+        Some(())
+    }
+
+    fn lex_custom_selector_decl(&mut self, lexer: &mut Lexer<'s>) -> Option<()> {
+        lexer.consume_white_space_and_comments()?;
+        let start = lexer.cur_pos()?;
+        if lexer.cur()? != C_COLON
+            || lexer.peek()? != C_HYPHEN_MINUS
+            || lexer.peek2()? != C_HYPHEN_MINUS
+        {
+            self.handle_warning.handle_warning(Warning {
+                range: Range::new(start, lexer.peek2_pos()?),
+                kind: WarningKind::Unexpected {
+                    message: "Expected starts with ':--' during parsing of '@custom-selector'",
+                },
+            });
+            return Some(());
+        }
+        lexer.consume();
+        let name_start = lexer.cur_pos()?;
+        lexer.consume_ident_sequence()?;
+        let end = lexer.cur_pos()?;
+        self.handle_dependency
+            .handle_dependency(Dependency::CustomSelectorDecl {
+                name: lexer.slice(name_start + 2, end)?,
+                range: Range::new(name_start, end),
+                local: self.is_local_scope(self.local_scope.custom_selector),
+            });
+        Some(())
+    }
+
     fn lex_local_keyframes_decl(&mut self, lexer: &mut Lexer<'s>) -> Option<()> {
         lexer.consume_white_space_and_comments()?;
         let mut is_function = false;
@@ -1177,10 +2095,12 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
             let start = lexer.cur_pos()?;
             lexer.consume_potential_pseudo(self)?;
             let end = lexer.cur_pos()?;
-            let pseudo = lexer.slice(start, end)?;
+            // ':local' and ':global' are replaced with the white space after them
+            let pseudo = lexer.slice(start, end)?.trim_end_matches(is_white_space);
             let mode_data = self.mode_data.as_ref().unwrap();
-            if mode_data.is_pure_mode() && pseudo.eq_ignore_ascii_case(":global(")
-                || pseudo.eq_ignore_ascii_case(":global")
+            if mode_data.is_pure_mode()
+                && (pseudo.eq_ignore_ascii_case(":global(")
+                    || pseudo.eq_ignore_ascii_case(":global"))
             {
                 self.handle_warning.handle_warning(Warning {
                     range: Range::new(start, end),
@@ -1218,7 +2138,7 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
         lexer.consume_ident_sequence()?;
         let end = lexer.cur_pos()?;
         let mode_data = self.mode_data.as_mut().unwrap();
-        if mode_data.is_current_local_mode() {
+        if mode_data.is_current_local_keyframes_mode() {
             self.handle_dependency
                 .handle_dependency(Dependency::LocalKeyframesDecl {
                     name: lexer.slice(start, end)?,
@@ -1300,6 +2220,51 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
             });
             return Some(());
         }
+        self.in_counter_style_block = true;
+        Some(())
+    }
+
+    fn handle_local_counter_style_system_dependency(&mut self, lexer: &Lexer<'s>) -> Option<()> {
+        let system = self.in_counter_style_system_property.as_mut().unwrap();
+        if let Some(range) = system.take_rename(self.balanced.len()) {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalCounterStyle {
+                    name: lexer.slice(range.start, range.end)?,
+                    range,
+                });
+        }
+        Some(())
+    }
+
+    fn handle_local_counter_dependency(&mut self, lexer: &Lexer<'s>) -> Option<()> {
+        let counter = self.in_counter_property.as_mut().unwrap();
+        if let Some(range) = counter.take_rename(self.balanced.len()) {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalCounter {
+                    name: lexer.slice(range.start, range.end)?,
+                    range,
+                });
+        }
+        Some(())
+    }
+
+    fn lex_local_counter(&mut self, lexer: &mut Lexer<'s>) -> Option<()> {
+        lexer.consume_white_space_and_comments()?;
+        let start = lexer.cur_pos()?;
+        // The counter name may come from 'var()'
+        if !start_ident_sequence(lexer.cur()?, lexer.peek()?, lexer.peek2()?) {
+            return Some(());
+        }
+        lexer.consume_ident_sequence()?;
+        let end = lexer.cur_pos()?;
+        let name = lexer.slice(start, end)?;
+        if CounterReserved.check(name) {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalCounter {
+                    name,
+                    range: Range::new(start, end),
+                });
+        }
         Some(())
     }
 
@@ -1315,14 +2280,438 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
         Some(())
     }
 
-    fn handle_local_font_palette_dependency(&mut self, lexer: &Lexer<'s>) -> Option<()> {
-        let font_palette = self.in_font_palette_property.as_mut().unwrap();
-        if let Some(range) = font_palette.take_rename(self.balanced.len()) {
-            self.handle_dependency
-                .handle_dependency(Dependency::LocalFontPalette {
-                    name: lexer.slice(range.start + 2, range.end)?,
-                    range,
-                });
+    fn handle_local_font_palette_dependency(&mut self, lexer: &Lexer<'s>) -> Option<()> {
+        let font_palette = self.in_font_palette_property.as_mut().unwrap();
+        if let Some(range) = font_palette.take_rename(self.balanced.len()) {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalFontPalette {
+                    name: lexer.slice(range.start + 2, range.end)?,
+                    range,
+                });
+        }
+        Some(())
+    }
+
+    fn lex_local_container(&mut self, lexer: &mut Lexer<'s>) -> Option<()> {
+        lexer.consume_white_space_and_comments()?;
+        let mut is_function = false;
+        if lexer.cur()? == C_COLON {
+            let start = lexer.cur_pos()?;
+            lexer.consume_potential_pseudo(self)?;
+            let end = lexer.cur_pos()?;
+            // ':local' and ':global' are replaced with the white space after them
+            let pseudo = lexer.slice(start, end)?.trim_end_matches(is_white_space);
+            let mode_data = self.mode_data.as_ref().unwrap();
+            if mode_data.is_pure_mode()
+                && (pseudo.eq_ignore_ascii_case(":global(")
+                    || pseudo.eq_ignore_ascii_case(":global"))
+            {
+                self.handle_warning.handle_warning(Warning {
+                    range: Range::new(start, end),
+                    kind: WarningKind::NotPure {
+                        message: "'@container :global' is not allowed in pure mode",
+                    },
+                });
+            }
+            is_function =
+                pseudo.eq_ignore_ascii_case(":local(") || pseudo.eq_ignore_ascii_case(":global(");
+            if !is_function
+                && !pseudo.eq_ignore_ascii_case(":local")
+                && !pseudo.eq_ignore_ascii_case(":global")
+            {
+                self.handle_warning.handle_warning(Warning {
+                    range: Range::new(start, end),
+                    kind: WarningKind::Unexpected {
+                        message: "Expected ':local', ':local()', ':global', or ':global()' during parsing of '@container' name",
+                    }
+                });
+                return Some(());
+            }
+            lexer.consume_white_space_and_comments()?;
+        }
+        let start = lexer.cur_pos()?;
+        if !start_ident_sequence(lexer.cur()?, lexer.peek()?, lexer.peek2()?) {
+            // Unnamed container query
+            return Some(());
+        }
+        let mut name_lexer = lexer.clone();
+        name_lexer.consume_ident_sequence()?;
+        let end = name_lexer.cur_pos()?;
+        let name = lexer.slice(start, end)?;
+        // style(), scroll-state() or a reserved keyword
+        if name_lexer.cur()? == C_LEFT_PARENTHESIS || !ContainerReserved.check(name) {
+            return Some(());
+        }
+        *lexer = name_lexer;
+        let mode_data = self.mode_data.as_mut().unwrap();
+        if mode_data.is_current_local_mode() {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalContainer {
+                    name,
+                    range: Range::new(start, end),
+                });
+        }
+        if is_function {
+            lexer.consume_white_space_and_comments()?;
+            if lexer.cur()? != C_RIGHT_PARENTHESIS {
+                self.handle_warning.handle_warning(Warning {
+                    range: Range::new(lexer.cur_pos()?, lexer.peek_pos()?),
+                    kind: WarningKind::Unexpected {
+                        message: "Expected ')' during parsing of '@container :local(' or '@container :global('",
+                    }
+                });
+                return Some(());
+            }
+            self.handle_dependency
+                .handle_dependency(Dependency::Replace {
+                    content: "",
+                    range: Range::new(lexer.cur_pos()?, lexer.peek_pos()?),
+                });
+            mode_data.inside_mode_function -= 1;
+            self.balanced.pop_without_moda_data();
+            self.balanced.update_current_mode(mode_data);
+            lexer.consume();
+        }
+        Some(())
+    }
+
+    fn handle_local_container_dependency(&mut self, lexer: &Lexer<'s>) -> Option<()> {
+        let container = self.in_container_property.as_mut().unwrap();
+        if let Some(range) = container.take_rename(self.balanced.len()) {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalContainerDecl {
+                    name: lexer.slice(range.start, range.end)?,
+                    range,
+                });
+        }
+        Some(())
+    }
+
+    fn handle_local_view_transition_name_dependency(&mut self, lexer: &Lexer<'s>) -> Option<()> {
+        let view_transition_name = self.in_view_transition_name_property.as_mut().unwrap();
+        if let Some(range) = view_transition_name.take_rename(self.balanced.len()) {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalViewTransitionNameDecl {
+                    name: lexer.slice(range.start, range.end)?,
+                    range,
+                });
+        }
+        Some(())
+    }
+
+    fn handle_local_view_transition_class_dependency(&mut self, lexer: &Lexer<'s>) -> Option<()> {
+        let view_transition_class = self.in_view_transition_class_property.as_mut().unwrap();
+        if let Some(range) = view_transition_class.take_rename(self.balanced.len()) {
+            self.handle_dependency
+                .handle_dependency(Dependency::LocalViewTransitionClassDecl {
+                    name: lexer.slice(range.start, range.end)?,
+                    range,
+                });
+        }
+        Some(())
+    }
+
+    fn is_inside_supports_selector(&self) -> bool {
+        self.in_at_rule_prelude == Some(AtRulePrelude::Supports)
+            && self
+                .balanced
+                .0
+                .iter()
+                .any(|item| matches!(item.kind, BalancedItemKind::Selector))
+    }
+
+    fn is_inside_view_transition_pseudo(&self) -> bool {
+        matches!(self.balanced.last(), Some(last) if matches!(last.kind, BalancedItemKind::ViewTransition))
+    }
+
+    fn is_scoped_selector_end(&self) -> bool {
+        self.mode_data
+            .as_ref()
+            .is_some_and(|mode_data| mode_data.is_scoped_mode())
+            && self.scope_insertion != ScopeInsertion::Skip
+            && self.is_next_rule_prelude
+            && self.in_keyframes_block.is_none()
+            && self.balanced.is_only_mode_classes()
+    }
+
+    fn handle_scope_insertion(&mut self, lexer: &Lexer<'s>, end: Pos, slotted: bool) -> Option<()> {
+        let selector = trim_end_white_space_and_comments(lexer.slice(0, end)?);
+        let pos = compound_selector_end(selector) as Pos;
+        self.handle_dependency
+            .handle_dependency(Dependency::ScopeInsertion {
+                range: Range::new(pos, pos),
+                slotted,
+            });
+        Some(())
+    }
+
+    fn handle_global_scope_insertion(&mut self, lexer: &Lexer<'s>, start: Pos) -> Option<()> {
+        // '.a :global(.b)' and '.a > :global .b' are scoped as '.a[data-v] .b' and
+        // '.a[data-v] > .b', a selector starting with ':global' isn't scoped
+        let selector = trim_end_white_space_and_comments(lexer.slice(0, start)?);
+        let selector =
+            trim_end_white_space_and_comments(selector.trim_end_matches(['>', '+', '~']));
+        if !selector.is_empty() && !selector.ends_with([',', '(', '{', '}', ';']) {
+            self.handle_scope_insertion(lexer, selector.len() as Pos, false)?;
+        }
+        self.scope_insertion = ScopeInsertion::Done;
+        Some(())
+    }
+
+    fn lex_scoped_pseudo_function(
+        &mut self,
+        lexer: &mut Lexer<'s>,
+        start: Pos,
+        end: Pos,
+        kind: BalancedItemKind,
+    ) -> Option<()> {
+        let mut content = "";
+        if matches!(kind, BalancedItemKind::Deep) {
+            // '.a :deep(.b)' is scoped as '.a[data-v] .b', and ':deep(.b)' as '[data-v] .b'
+            let selector = trim_end_white_space_and_comments(lexer.slice(0, start)?);
+            let is_empty = selector.is_empty()
+                || selector.ends_with(['>', '+', '~', '|', ',', '(', '{', '}', ';']);
+            if is_empty {
+                self.handle_dependency
+                    .handle_dependency(Dependency::ScopeInsertion {
+                        range: Range::new(start, start),
+                        slotted: false,
+                    });
+            } else {
+                self.handle_scope_insertion(lexer, start, false)?;
+            }
+            if is_empty || selector.len() as Pos == start {
+                content = " ";
+            }
+        }
+        self.scope_insertion = ScopeInsertion::Done;
+        lexer.consume_white_space_and_comments()?;
+        self.handle_dependency
+            .handle_dependency(Dependency::Replace {
+                content,
+                range: Range::new(start, lexer.cur_pos()?),
+            });
+        self.balanced.push(
+            BalancedItem {
+                kind,
+                range: Range::new(start, end),
+            },
+            self.mode_data.as_mut(),
+        );
+        Some(())
+    }
+
+    fn handle_local_view_transition_pseudo(&mut self, dependency: Dependency<'s>) -> Option<()> {
+        let mode_data = self.mode_data.as_mut().unwrap();
+        if mode_data.is_current_local_mode() {
+            self.handle_dependency.handle_dependency(dependency);
+            if mode_data.is_pure_mode() {
+                mode_data.pure_global = None;
+            }
+        }
+        Some(())
+    }
+
+    fn lex_local_anchor(&mut self, lexer: &mut Lexer<'s>) -> Option<()> {
+        // The anchor name is optional, fallback to the implicit anchor element,
+        // and can come after the side or size, e.g. `anchor(right --a)`
+        loop {
+            lexer.consume_white_space_and_comments()?;
+            let start = lexer.cur_pos()?;
+            if !start_ident_sequence(lexer.cur()?, lexer.peek()?, lexer.peek2()?) {
+                return Some(());
+            }
+            let is_dashed = lexer.cur()? == C_HYPHEN_MINUS && lexer.peek()? == C_HYPHEN_MINUS;
+            let mut name_lexer = lexer.clone();
+            name_lexer.consume_ident_sequence()?;
+            // A function is lexed as is, e.g. `calc(`
+            if name_lexer.cur()? == C_LEFT_PARENTHESIS {
+                return Some(());
+            }
+            *lexer = name_lexer;
+            if is_dashed {
+                let end = lexer.cur_pos()?;
+                self.handle_dependency
+                    .handle_dependency(Dependency::LocalAnchor {
+                        name: lexer.slice(start + 2, end)?,
+                        range: Range::new(start, end),
+                    });
+            }
+        }
+    }
+
+    fn lex_local_grid_areas(&mut self, lexer: &Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
+        // Skip the quotes, an unclosed string is still a valid string token
+        let content = lexer.slice(start + 1, end)?;
+        let content = content
+            .strip_suffix(|c| c == '"' || c == '\'')
+            .unwrap_or(content);
+        let mut name_start = None;
+        for (i, c) in content
+            .char_indices()
+            .chain(std::iter::once((content.len(), ' ')))
+        {
+            let pos = start + 1 + i as Pos;
+            if is_ident(c) {
+                if name_start.is_none() {
+                    name_start = Some(pos);
+                }
+                continue;
+            }
+            if let Some(name_start) = name_start.take() {
+                self.handle_dependency
+                    .handle_dependency(Dependency::LocalGridArea {
+                        name: lexer.slice(name_start, pos)?,
+                        range: Range::new(name_start, pos),
+                    });
+            }
+        }
+        Some(())
+    }
+
+    fn handle_local_grid_line_dependency(
+        &mut self,
+        lexer: &Lexer<'s>,
+        start: Pos,
+        end: Pos,
+    ) -> Option<()> {
+        let ident = lexer.slice(start, end)?;
+        // Implicit named lines of a grid area, `foo-start` and `foo-end` belong to area `foo`
+        let name = ident
+            .strip_suffix("-start")
+            .or_else(|| ident.strip_suffix("-end"))
+            .filter(|name| !name.is_empty())
+            .unwrap_or(ident);
+        let end = start + name.len() as Pos;
+        self.handle_dependency
+            .handle_dependency(Dependency::LocalGridLine {
+                name,
+                range: Range::new(start, end),
+            });
+        Some(())
+    }
+
+    fn lex_value(&mut self, lexer: &mut Lexer<'s>, start: Pos) -> Option<()> {
+        lexer.consume_white_space_and_comments()?;
+        let params_start = lexer.cur_pos()?;
+        let mut balanced: u32 = 0;
+        while let Some(c) = lexer.cur() {
+            if c == C_SEMICOLON && balanced == 0 || c == C_LEFT_CURLY || c == C_RIGHT_CURLY {
+                break;
+            }
+            if c == C_QUOTATION_MARK || c == C_APOSTROPHE {
+                lexer.consume_string(self, c)?;
+                continue;
+            }
+            if c == C_SOLIDUS && lexer.peek() == Some(C_ASTERISK) {
+                lexer.consume_comments()?;
+                continue;
+            }
+            if c == C_LEFT_PARENTHESIS {
+                balanced += 1;
+            } else if c == C_RIGHT_PARENTHESIS {
+                balanced = balanced.saturating_sub(1);
+            }
+            lexer.consume();
+        }
+        let params_end = lexer.cur_pos()?;
+        let params = lexer
+            .slice(params_start, params_end)?
+            .trim_end_matches(is_white_space);
+        if lexer.cur() == Some(C_SEMICOLON) {
+            lexer.consume();
+        }
+        let end = lexer.cur_pos()?;
+
+        if let Some((names, from)) = split_value_import(params) {
+            let names = names
+                .strip_prefix('(')
+                .and_then(|names| names.strip_suffix(')'))
+                .unwrap_or(names);
+            for item in names.split(C_COMMA) {
+                let mut words = item.split(is_white_space).filter(|w| !w.is_empty());
+                let (name, alias) = match (words.next(), words.next(), words.next(), words.next()) {
+                    (Some(name), None, None, None) => (name, name),
+                    (Some(name), Some("as"), Some(alias), None) => (name, alias),
+                    _ => {
+                        self.handle_warning.handle_warning(Warning {
+                            range: Range::new(params_start, params_end),
+                            kind: WarningKind::Unexpected {
+                                message:
+                                    "Expected 'name' or 'name as alias' during parsing of '@value'",
+                            },
+                        });
+                        continue;
+                    }
+                };
+                self.value_names.insert(alias);
+                self.handle_dependency
+                    .handle_dependency(Dependency::ValueImport { name, alias, from });
+            }
+        } else {
+            let name_len = params.find(|c| !is_ident(c)).unwrap_or(params.len());
+            if name_len == 0 {
+                self.handle_warning.handle_warning(Warning {
+                    range: Range::new(params_start, params_end),
+                    kind: WarningKind::Unexpected {
+                        message: "Expected ident during parsing of '@value'",
+                    },
+                });
+                return Some(());
+            }
+            let (name, value) = params.split_at(name_len);
+            let value = value.strip_prefix(C_COLON).unwrap_or(value);
+            self.value_names.insert(name);
+            self.handle_dependency
+                .handle_dependency(Dependency::ValueDecl {
+                    name,
+                    value: value.trim_matches(is_white_space),
+                });
+        }
+        self.handle_dependency
+            .handle_dependency(Dependency::Replace {
+                content: "",
+                range: Range::new(start, end),
+            });
+        Some(())
+    }
+
+    fn is_value_usage(&self, lexer: &Lexer<'s>, ident: &str) -> Option<bool> {
+        is_symbol_usage(lexer, &self.value_names, ident)
+    }
+
+    fn is_icss_import_usage(&self, lexer: &Lexer<'s>, ident: &str) -> Option<bool> {
+        is_symbol_usage(lexer, &self.icss_import_aliases, ident)
+    }
+
+    fn lex_icss_import_usages(&mut self, lexer: &Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
+        if self.icss_import_aliases.is_empty() {
+            return Some(());
+        }
+        let value = lexer.slice(start, end)?;
+        let mut i = 0;
+        while let Some(c) = value[i..].chars().next() {
+            if value[i..].starts_with("/*") {
+                i = value[i + 2..]
+                    .find("*/")
+                    .map_or(value.len(), |comment_end| i + 2 + comment_end + 2);
+                continue;
+            }
+            if !is_ident(c) {
+                i += c.len_utf8();
+                continue;
+            }
+            let len = value[i..].find(|c| !is_ident(c)).unwrap_or(value.len() - i);
+            let name = &value[i..i + len];
+            if self.icss_import_aliases.contains(name) {
+                let name_start = start + i as Pos;
+                self.handle_dependency
+                    .handle_dependency(Dependency::ICSSImportUsage {
+                        name,
+                        range: Range::new(name_start, name_start + len as Pos),
+                    });
+            }
+            i += len;
         }
         Some(())
     }
@@ -1473,7 +2862,7 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> LexDependencies<'s, D, W
 
 impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDependencies<'s, D, W> {
     fn is_selector(&mut self, _: &mut Lexer) -> Option<bool> {
-        Some(self.is_next_rule_prelude)
+        Some(self.is_next_rule_prelude || self.is_inside_supports_selector())
     }
 
     fn url(
@@ -1543,6 +2932,9 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                 }
             }
             Scope::InBlock => {
+                if matches!(self.in_grid_property, Some(GridProperty::Template)) {
+                    return self.lex_local_grid_areas(lexer, start, end);
+                }
                 let Some(last) = self.balanced.last() else {
                     return Some(());
                 };
@@ -1565,6 +2957,19 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
 
     fn at_keyword(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
         let name = lexer.slice(start, end)?;
+        self.in_at_rule_prelude =
+            if name.eq_ignore_ascii_case("@media") || name.eq_ignore_ascii_case("@custom-media") {
+                Some(AtRulePrelude::Media)
+            } else if name.eq_ignore_ascii_case("@supports") {
+                Some(AtRulePrelude::Supports)
+            } else {
+                None
+            };
+        // Neither the prelude nor the selectors of '@scope' and '@custom-selector' are scoped
+        self.scope_insertion = ScopeInsertion::Skip;
+        if name.eq_ignore_ascii_case("@layer") {
+            self.lex_layer_names(lexer)?;
+        }
         if name.eq_ignore_ascii_case("@namespace") {
             self.scope = Scope::AtNamespaceInvalid;
             self.handle_warning.handle_warning(Warning {
@@ -1581,11 +2986,32 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                 return Some(());
             }
             self.scope = Scope::InAtImport(ImportData::new(start));
+        } else if name.eq_ignore_ascii_case("@custom-media") {
+            self.lex_custom_media_decl(lexer)?;
+            self.is_next_rule_prelude = false;
+        } else if name.eq_ignore_ascii_case("@custom-selector") {
+            self.lex_custom_selector_decl(lexer)?;
+            // The rest of the prelude is a selector list
+            self.is_next_rule_prelude = true;
+            if let Some(mode_data) = &mut self.mode_data {
+                mode_data.pure_global = None;
+            }
         } else if self.mode_data.is_some() {
-            if name.eq_ignore_ascii_case("@keyframes")
+            if name.eq_ignore_ascii_case("@value") && matches!(self.scope, Scope::TopLevel) {
+                self.lex_value(lexer, start)?;
+                let mode_data = self.mode_data.as_mut().unwrap();
+                mode_data.composes_local_classes.reset_to_initial();
+                // The ';' is consumed by '@value'
+                self.scope_insertion = ScopeInsertion::Pending;
+                return Some(());
+            } else if name.eq_ignore_ascii_case("@keyframes")
                 || with_vendor_prefixed_eq(name, "keyframes", true)
             {
+                self.in_keyframes_block = Some(self.rule_nesting_level);
                 self.lex_local_keyframes_decl(lexer)?;
+            } else if self.mode_data.as_ref().unwrap().is_scoped_mode() {
+                // Only keyframes are local in scoped mode
+                self.is_next_rule_prelude = name.eq_ignore_ascii_case("@scope");
             } else if name.eq_ignore_ascii_case("@property") {
                 self.lex_local_dashed_ident_decl(
                     lexer,
@@ -1603,6 +3029,27 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                         },
                     },
                 )?;
+            } else if name.eq_ignore_ascii_case("@position-try") {
+                self.lex_local_dashed_ident_decl(
+                    lexer,
+                    |name, range| Dependency::LocalPositionTryDecl { name, range },
+                    |range| Warning {
+                        range,
+                        kind: WarningKind::Unexpected {
+                            message: "Expected starts with '--' during parsing of '@position-try'",
+                        },
+                    },
+                    |range| Warning {
+                        range,
+                        kind: WarningKind::Unexpected {
+                            message: "Expected '{' during parsing of '@position-try'",
+                        },
+                    },
+                )?;
+            } else if name.eq_ignore_ascii_case("@container") {
+                self.lex_local_container(lexer)?;
+                self.in_at_rule_prelude = Some(AtRulePrelude::Container);
+                self.is_next_rule_prelude = false;
             } else if name.eq_ignore_ascii_case("@counter-style") {
                 self.lex_local_counter_style_decl(lexer)?;
             } else if name.eq_ignore_ascii_case("@font-palette-values") {
@@ -1639,6 +3086,11 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
     }
 
     fn semicolon(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
+        self.in_at_rule_prelude = None;
+        self.scope_insertion = ScopeInsertion::Pending;
+        if self.rule_nesting_level == 0 {
+            self.rule_start = end;
+        }
         match self.scope {
             Scope::InAtImport(ref import_data) => {
                 let Some(url) = import_data.url else {
@@ -1722,6 +3174,10 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                     .unwrap_or(url_range)
                     .end;
                 let media = self.get_media(lexer, last_end, start);
+                let layer_start = import_data
+                    .layer_range()
+                    .zip(layer)
+                    .map(|(range, value)| range.end - 1 - value.len() as u32);
                 self.handle_dependency
                     .handle_dependency(Dependency::Import {
                         request: url,
@@ -1730,16 +3186,27 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                         supports,
                         media,
                     });
+                if let Some((value, start)) = layer.zip(layer_start) {
+                    self.handle_import_layer_names(value, start);
+                }
                 self.scope = Scope::TopLevel;
             }
             Scope::AtImportInvalid | Scope::AtNamespaceInvalid => {
                 self.scope = Scope::TopLevel;
             }
+            Scope::TopLevel => {
+                // End of a statement at-rule, e.g. '@custom-media' or '@custom-selector'
+                if let Some(mode_data) = &mut self.mode_data {
+                    mode_data.pure_global = Some(end);
+                    mode_data.composes_local_classes.reset_to_initial();
+                    self.is_next_rule_prelude = true;
+                }
+            }
             Scope::InBlock => {
                 if let Some(mode_data) = &mut self.mode_data {
                     mode_data.pure_global = Some(end);
 
-                    if mode_data.is_property_local_mode() {
+                    if mode_data.is_property_local_keyframes_mode() {
                         if self.in_animation_property.is_some() {
                             self.handle_local_keyframes_dependency(lexer)?;
                             self.exit_animation_property();
@@ -1748,16 +3215,37 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                             self.handle_local_counter_style_dependency(lexer)?;
                             self.exit_list_style_property();
                         }
+                        if self.in_counter_style_system_property.is_some() {
+                            self.handle_local_counter_style_system_dependency(lexer)?;
+                            self.exit_counter_style_system_property();
+                        }
+                        if self.in_counter_property.is_some() {
+                            self.exit_counter_property();
+                        }
                         if self.in_font_palette_property.is_some() {
                             self.handle_local_font_palette_dependency(lexer)?;
                             self.exit_font_palette_property();
                         }
+                        if self.in_container_property.is_some() {
+                            self.exit_container_property();
+                        }
+                        if self.in_view_transition_name_property.is_some() {
+                            self.exit_view_transition_name_property();
+                        }
+                        if self.in_view_transition_class_property.is_some() {
+                            self.exit_view_transition_class_property();
+                        }
+                        if self.in_grid_property.is_some() {
+                            self.exit_grid_property();
+                        }
+                        if self.in_dashed_ident_property.is_some() {
+                            self.exit_dashed_ident_property();
+                        }
                     }
 
                     self.is_next_rule_prelude = self.is_next_nested_syntax(lexer)?;
                 }
             }
-            _ => {}
         }
         Some(())
     }
@@ -1776,8 +3264,26 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
         let Some(mode_data) = &self.mode_data else {
             return Some(());
         };
-        if mode_data.is_current_local_mode() && name.eq_ignore_ascii_case("var(") {
-            self.lex_local_var(lexer)?;
+        if mode_data.is_current_local_mode() {
+            if name.eq_ignore_ascii_case("var(") {
+                self.lex_local_var(lexer)?;
+            } else if (name.eq_ignore_ascii_case("counter(")
+                || name.eq_ignore_ascii_case("counters("))
+                && mode_data.is_property_local_mode()
+            {
+                self.lex_local_counter(lexer)?;
+            } else if name.eq_ignore_ascii_case("reversed(")
+                && self.in_counter_property.is_some()
+                && self.balanced.len() == 1
+                && mode_data.is_property_local_mode()
+            {
+                // 'counter-reset: reversed(a)' resets the counter 'a'
+                self.lex_local_counter(lexer)?;
+            } else if name.eq_ignore_ascii_case("anchor(")
+                || name.eq_ignore_ascii_case("anchor-size(")
+            {
+                self.lex_local_anchor(lexer)?;
+            }
         }
         Some(())
     }
@@ -1792,6 +3298,21 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
         let Some(last) = self.balanced.pop(self.mode_data.as_mut()) else {
             return Some(());
         };
+        if matches!(
+            last.kind,
+            BalancedItemKind::Deep | BalancedItemKind::Slotted
+        ) {
+            let start = start - self.back_white_space_and_comments_distance(lexer, start)?;
+            if matches!(last.kind, BalancedItemKind::Slotted) {
+                self.handle_scope_insertion(lexer, start, true)?;
+            }
+            self.handle_dependency
+                .handle_dependency(Dependency::Replace {
+                    content: "",
+                    range: Range::new(start, end),
+                });
+            return Some(());
+        }
         if let Some(mode_data) = &mut self.mode_data {
             let mut is_function = last.kind.is_mode_function();
             if last.kind.is_mode_class() {
@@ -1842,14 +3363,69 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
     }
 
     fn ident(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
+        if self.in_at_rule_prelude == Some(AtRulePrelude::Media)
+            && matches!(self.balanced.last(), Some(last) if matches!(last.kind, BalancedItemKind::Other))
+        {
+            let name = lexer.slice(start, end)?;
+            if let Some(name) = name.strip_prefix("--") {
+                self.handle_dependency
+                    .handle_dependency(Dependency::CustomMedia {
+                        name,
+                        range: Range::new(start, end),
+                        local: self.is_local_scope(self.local_scope.custom_media),
+                    });
+                return Some(());
+            }
+        }
+        if self.mode_data.is_some() && self.is_inside_view_transition_pseudo() {
+            let name = lexer.slice(start, end)?;
+            if name != "root" {
+                self.handle_local_view_transition_pseudo(Dependency::LocalViewTransitionName {
+                    name,
+                    range: Range::new(start, end),
+                })?;
+            }
+            return Some(());
+        }
         match self.scope {
             Scope::InBlock => {
-                let Some(mode_data) = &mut self.mode_data else {
+                if self.mode_data.is_none() {
                     return Some(());
-                };
+                }
 
                 let ident = lexer.slice(start, end)?;
+                if self.is_value_usage(lexer, ident)? {
+                    self.handle_dependency
+                        .handle_dependency(Dependency::ValueUsage {
+                            name: ident,
+                            range: Range::new(start, end),
+                        });
+                    return Some(());
+                }
+                if self.is_icss_import_usage(lexer, ident)? {
+                    self.handle_dependency
+                        .handle_dependency(Dependency::ICSSImportUsage {
+                            name: ident,
+                            range: Range::new(start, end),
+                        });
+                    return Some(());
+                }
+                let mode_data = self.mode_data.as_mut().unwrap();
                 if mode_data.is_property_local_mode() {
+                    if matches!(self.balanced.last(), Some(last) if matches!(last.kind, BalancedItemKind::Counter))
+                    {
+                        // The counter name is lexed by 'counter(', so this is the counter style
+                        if ListStyleReserved.check(ident) {
+                            self.handle_dependency.handle_dependency(
+                                Dependency::LocalCounterStyle {
+                                    name: ident,
+                                    range: Range::new(start, end),
+                                },
+                            );
+                        }
+                        return Some(());
+                    }
+
                     if let Some(animation) = &mut self.in_animation_property {
                         // Not inside functions
                         if self.balanced.is_empty() {
@@ -1877,15 +3453,96 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                         return Some(());
                     }
 
+                    if let Some(system) = &mut self.in_counter_style_system_property {
+                        // Not inside functions
+                        if self.balanced.is_empty() {
+                            system.set_rename(ident, Range::new(start, end));
+                        }
+                        return Some(());
+                    }
+
+                    if let Some(counter) = &mut self.in_counter_property {
+                        // Not inside functions, multiple counters are separated by spaces
+                        if self.balanced.is_empty() {
+                            counter.set_rename(ident, Range::new(start, end));
+                            self.handle_local_counter_dependency(lexer)?;
+                        }
+                        return Some(());
+                    }
+
+                    if let Some(container) = &mut self.in_container_property {
+                        // Not inside functions, multiple names are separated by spaces
+                        if self.balanced.is_empty() {
+                            container.set_rename(ident, Range::new(start, end));
+                            self.handle_local_container_dependency(lexer)?;
+                        }
+                        return Some(());
+                    }
+
+                    if let Some(view_transition_name) = &mut self.in_view_transition_name_property {
+                        // Not inside functions
+                        if self.balanced.is_empty() {
+                            view_transition_name.set_rename(ident, Range::new(start, end));
+                            self.handle_local_view_transition_name_dependency(lexer)?;
+                        }
+                        return Some(());
+                    }
+
+                    if let Some(view_transition_class) = &mut self.in_view_transition_class_property
+                    {
+                        // Not inside functions, multiple classes are separated by spaces
+                        if self.balanced.is_empty() {
+                            view_transition_class.set_rename(ident, Range::new(start, end));
+                            self.handle_local_view_transition_class_dependency(lexer)?;
+                        }
+                        return Some(());
+                    }
+
+                    if let Some(grid) = self.in_grid_property {
+                        match grid {
+                            GridProperty::TemplateLineNames => {
+                                self.handle_local_grid_line_dependency(lexer, start, end)?;
+                            }
+                            // Not inside functions
+                            GridProperty::Placement
+                                if self.balanced.is_empty()
+                                    && !is_grid_placement_reserved(ident) =>
+                            {
+                                self.handle_local_grid_line_dependency(lexer, start, end)?;
+                            }
+                            _ => {}
+                        }
+                        return Some(());
+                    }
+
+                    if let Some(property) = self.in_dashed_ident_property {
+                        // Not inside functions
+                        if self.balanced.is_empty() {
+                            if property.allow_from() && ident.starts_with("--") {
+                                return self.lex_local_dashed_ident_from(
+                                    lexer,
+                                    start,
+                                    end,
+                                    |name, range, from| property.dependency(name, range, from),
+                                );
+                            }
+                            if let Some(name) = ident.strip_prefix("--") {
+                                self.handle_dependency
+                                    .handle_dependency(property.dependency(
+                                        name,
+                                        Range::new(start, end),
+                                        None,
+                                    ));
+                            }
+                        }
+                        return Some(());
+                    }
+
                     if let Some(name) = ident.strip_prefix("--") {
                         return self.lex_local_var_decl(lexer, name, start, end);
                     }
 
-                    if ident.eq_ignore_ascii_case("animation")
-                        || ident.eq_ignore_ascii_case("animation-name")
-                        || with_vendor_prefixed_eq(ident, "animation", false)
-                        || with_vendor_prefixed_eq(ident, "animation-name", false)
-                    {
+                    if is_animation_property(ident) {
                         self.enter_animation_property();
                         return Some(());
                     }
@@ -1897,15 +3554,87 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                         return Some(());
                     }
 
+                    if ident.eq_ignore_ascii_case("counter-reset")
+                        || ident.eq_ignore_ascii_case("counter-increment")
+                        || ident.eq_ignore_ascii_case("counter-set")
+                    {
+                        self.enter_counter_property();
+                        return Some(());
+                    }
+
+                    if self.in_counter_style_block {
+                        if ident.eq_ignore_ascii_case("system") {
+                            self.enter_counter_style_system_property();
+                            return Some(());
+                        }
+                        if ident.eq_ignore_ascii_case("fallback") {
+                            self.enter_list_style_property();
+                            return Some(());
+                        }
+                    }
+
                     if ident.eq_ignore_ascii_case("font-palette") {
                         self.enter_font_palette_property();
                         return Some(());
                     }
+
+                    if ident.eq_ignore_ascii_case("container-name")
+                        || ident.eq_ignore_ascii_case("container")
+                    {
+                        self.enter_container_property();
+                        return Some(());
+                    }
+
+                    if ident.eq_ignore_ascii_case("view-transition-name") {
+                        self.enter_view_transition_name_property();
+                        return Some(());
+                    }
+
+                    if ident.eq_ignore_ascii_case("view-transition-class") {
+                        self.enter_view_transition_class_property();
+                        return Some(());
+                    }
+
+                    // Not the value of `display: grid`
+                    if let Some(grid) = GridProperty::new(ident) {
+                        if is_property_name(lexer)? {
+                            self.enter_grid_property(grid);
+                            return Some(());
+                        }
+                    }
+
+                    if let Some(property) = DashedIdentProperty::new(ident) {
+                        self.enter_dashed_ident_property(property);
+                        return Some(());
+                    }
+                } else if mode_data.is_property_local_keyframes_mode() {
+                    // Only keyframes are local in scoped mode
+                    if let Some(animation) = &mut self.in_animation_property {
+                        if self.balanced.is_empty() {
+                            animation.set_rename(ident, Range::new(start, end));
+                        }
+                        return Some(());
+                    }
+
+                    if is_animation_property(ident) {
+                        self.enter_animation_property();
+                        return Some(());
+                    }
                 }
 
                 if ident.eq_ignore_ascii_case("composes")
                     || ident.eq_ignore_ascii_case("compose-with")
                 {
+                    // Classes aren't renamed in scoped mode
+                    if mode_data.is_scoped_mode() {
+                        self.handle_warning.handle_warning(Warning {
+                            range: Range::new(start, end),
+                            kind: WarningKind::UnexpectedComposition {
+                                message: "not supported in scoped mode",
+                            },
+                        });
+                        return Some(());
+                    }
                     if self.block_nesting_level != 1 {
                         self.handle_warning.handle_warning(Warning {
                             range: Range::new(start, end),
@@ -1939,9 +3668,24 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                 }
             }
             Scope::TopLevel => {
-                let Some(mode_data) = &mut self.mode_data else {
+                if self.mode_data.is_none() {
                     return Some(());
-                };
+                }
+                let ident = lexer.slice(start, end)?;
+                if self.is_value_usage(lexer, ident)? {
+                    self.handle_dependency
+                        .handle_dependency(Dependency::ValueUsage {
+                            name: ident,
+                            range: Range::new(start, end),
+                        });
+                } else if self.is_icss_import_usage(lexer, ident)? {
+                    self.handle_dependency
+                        .handle_dependency(Dependency::ICSSImportUsage {
+                            name: ident,
+                            range: Range::new(start, end),
+                        });
+                }
+                let mode_data = self.mode_data.as_mut().unwrap();
                 mode_data.composes_local_classes.invalidate();
             }
             _ => {}
@@ -1950,9 +3694,10 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
     }
 
     fn class(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
-        let Some(mode_data) = &mut self.mode_data else {
+        // e.g. the 'layer(reset.base)' of '@import'
+        if self.mode_data.is_none() || matches!(self.scope, Scope::InAtImport(_)) {
             return Some(());
-        };
+        }
         let name = lexer.slice(start, end)?;
         if name == "." {
             self.handle_warning.handle_warning(Warning {
@@ -1963,6 +3708,29 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
             });
             return Some(());
         }
+        if self.is_inside_view_transition_pseudo() {
+            return self.handle_local_view_transition_pseudo(
+                Dependency::LocalViewTransitionClass {
+                    name: lexer.slice(start + 1, end)?,
+                    range: Range::new(start + 1, end),
+                },
+            );
+        }
+        let mode_data = self.mode_data.as_mut().unwrap();
+        // An imported alias, unless it's explicitly local, e.g. ':local(.alias)'
+        if self.icss_import_aliases.contains(&name[1..])
+            && !(mode_data.is_current_local_mode() && mode_data.is_mode_explicit())
+        {
+            self.handle_dependency
+                .handle_dependency(Dependency::ICSSImportUsage {
+                    name: &name[1..],
+                    range: Range::new(start + 1, end),
+                });
+            if self.block_nesting_level == 0 {
+                mode_data.composes_local_classes.invalidate();
+            }
+            return Some(());
+        }
         if mode_data.is_current_local_mode() {
             self.handle_dependency
                 .handle_dependency(Dependency::LocalClass {
@@ -1997,6 +3765,19 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
             });
             return Some(());
         }
+        if self.icss_import_aliases.contains(&name[1..])
+            && !(mode_data.is_current_local_mode() && mode_data.is_mode_explicit())
+        {
+            self.handle_dependency
+                .handle_dependency(Dependency::ICSSImportUsage {
+                    name: &name[1..],
+                    range: Range::new(start + 1, end),
+                });
+            if self.block_nesting_level == 0 {
+                mode_data.composes_local_classes.invalidate();
+            }
+            return Some(());
+        }
         if mode_data.is_current_local_mode() {
             self.handle_dependency
                 .handle_dependency(Dependency::LocalId {
@@ -2016,7 +3797,13 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
         Some(())
     }
 
-    fn left_curly_bracket(&mut self, lexer: &mut Lexer, start: Pos, _: Pos) -> Option<()> {
+    fn left_curly_bracket(&mut self, lexer: &mut Lexer<'s>, start: Pos, _: Pos) -> Option<()> {
+        if self.scope_insertion == ScopeInsertion::Pending && self.is_scoped_selector_end() {
+            self.handle_scope_insertion(lexer, start, false)?;
+        }
+        self.scope_insertion = ScopeInsertion::Pending;
+        self.in_at_rule_prelude = None;
+        self.rule_nesting_level += 1;
         match self.scope {
             Scope::TopLevel => {
                 self.allow_import_at_rule = false;
@@ -2069,11 +3856,14 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
     }
 
     fn right_curly_bracket(&mut self, lexer: &mut Lexer<'s>, _: Pos, end: Pos) -> Option<()> {
+        // There are no nested blocks inside '@counter-style'
+        self.in_counter_style_block = false;
+        self.scope_insertion = ScopeInsertion::Pending;
         if matches!(self.scope, Scope::InBlock) {
             if let Some(mode_data) = &mut self.mode_data {
                 mode_data.pure_global = Some(end);
 
-                if mode_data.is_property_local_mode() {
+                if mode_data.is_property_local_keyframes_mode() {
                     if self.in_animation_property.is_some() {
                         self.handle_local_keyframes_dependency(lexer)?;
                         self.exit_animation_property();
@@ -2082,10 +3872,32 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                         self.handle_local_counter_style_dependency(lexer)?;
                         self.exit_list_style_property();
                     }
+                    if self.in_counter_style_system_property.is_some() {
+                        self.handle_local_counter_style_system_dependency(lexer)?;
+                        self.exit_counter_style_system_property();
+                    }
+                    if self.in_counter_property.is_some() {
+                        self.exit_counter_property();
+                    }
                     if self.in_font_palette_property.is_some() {
                         self.handle_local_font_palette_dependency(lexer)?;
                         self.exit_font_palette_property();
                     }
+                    if self.in_container_property.is_some() {
+                        self.exit_container_property();
+                    }
+                    if self.in_view_transition_name_property.is_some() {
+                        self.exit_view_transition_name_property();
+                    }
+                    if self.in_view_transition_class_property.is_some() {
+                        self.exit_view_transition_class_property();
+                    }
+                    if self.in_grid_property.is_some() {
+                        self.exit_grid_property();
+                    }
+                    if self.in_dashed_ident_property.is_some() {
+                        self.exit_dashed_ident_property();
+                    }
                 }
             }
 
@@ -2102,11 +3914,45 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
                 self.is_next_rule_prelude = self.is_next_nested_syntax(lexer)?;
             }
         }
+        self.handle_rule_end(lexer, end)?;
+        if self.in_keyframes_block == Some(self.rule_nesting_level) {
+            self.in_keyframes_block = None;
+        }
+        Some(())
+    }
+
+    fn left_square_bracket(&mut self, _: &mut Lexer<'s>, _: Pos, _: Pos) -> Option<()> {
+        if matches!(self.in_grid_property, Some(GridProperty::Template)) {
+            self.in_grid_property = Some(GridProperty::TemplateLineNames);
+        }
+        Some(())
+    }
+
+    fn right_square_bracket(&mut self, _: &mut Lexer<'s>, _: Pos, _: Pos) -> Option<()> {
+        if matches!(self.in_grid_property, Some(GridProperty::TemplateLineNames)) {
+            self.in_grid_property = Some(GridProperty::Template);
+        }
         Some(())
     }
 
     fn pseudo_function(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
         let name = lexer.slice(start, end)?;
+        if self.scope_insertion == ScopeInsertion::Pending && self.is_scoped_selector_end() {
+            if name.eq_ignore_ascii_case(":deep(") {
+                return self.lex_scoped_pseudo_function(lexer, start, end, BalancedItemKind::Deep);
+            }
+            if name.eq_ignore_ascii_case(":slotted(") {
+                return self.lex_scoped_pseudo_function(
+                    lexer,
+                    start,
+                    end,
+                    BalancedItemKind::Slotted,
+                );
+            }
+            if name.eq_ignore_ascii_case(":global(") {
+                self.handle_global_scope_insertion(lexer, start)?;
+            }
+        }
         if let Some(mode_data) = &mut self.mode_data {
             if name.eq_ignore_ascii_case(":import(") {
                 self.lex_icss_import(lexer);
@@ -2143,10 +3989,25 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
     }
 
     fn pseudo_class(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
+        let name = lexer.slice(start, end)?;
+        if let Some(custom_selector) = name.strip_prefix(":--") {
+            self.handle_dependency
+                .handle_dependency(Dependency::CustomSelector {
+                    name: custom_selector,
+                    range: Range::new(start + 1, end),
+                    local: self.is_local_scope(self.local_scope.custom_selector),
+                });
+            return Some(());
+        }
+        if name.eq_ignore_ascii_case(":global")
+            && self.scope_insertion == ScopeInsertion::Pending
+            && self.is_scoped_selector_end()
+        {
+            self.handle_global_scope_insertion(lexer, start)?;
+        }
         let Some(mode_data) = &mut self.mode_data else {
             return Some(());
         };
-        let name = lexer.slice(start, end)?;
         if name.eq_ignore_ascii_case(":global") || name.eq_ignore_ascii_case(":local") {
             if mode_data.is_inside_mode_function() {
                 self.handle_warning.handle_warning(Warning {
@@ -2206,6 +4067,22 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
     }
 
     fn comma(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()> {
+        if self.is_inside_supports_selector() {
+            return Some(());
+        }
+        if self.in_at_rule_prelude == Some(AtRulePrelude::Container) {
+            // Each query of '@container a (...), b (...)' has its own name
+            if self.balanced.is_empty() {
+                self.lex_local_container(lexer)?;
+            }
+            return Some(());
+        }
+        if self.is_scoped_selector_end() {
+            if self.scope_insertion == ScopeInsertion::Pending {
+                self.handle_scope_insertion(lexer, start, false)?;
+            }
+            self.scope_insertion = ScopeInsertion::Pending;
+        }
         let Some(mode_data) = &mut self.mode_data else {
             return Some(());
         };
@@ -2246,7 +4123,7 @@ impl<'s, D: HandleDependency<'s>, W: HandleWarning<'s>> Visitor<'s> for LexDepen
         }
 
         if matches!(self.scope, Scope::InBlock)
-            && mode_data.is_property_local_mode()
+            && mode_data.is_property_local_keyframes_mode()
             && self.in_animation_property.is_some()
         {
             self.handle_local_keyframes_dependency(lexer)?;
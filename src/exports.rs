@@ -0,0 +1,187 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::Dependency;
+use crate::HandleDependency;
+use crate::HandleLocalName;
+use crate::LocalKind;
+use crate::LocalName;
+
+/// One of the names an export stands for.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum ExportName<'s> {
+    /// A local name of this module, renamed by `handle_local_name`.
+    Local(String),
+    /// `composes: a from global`, or a value of `:export` and `@value`, used as is.
+    Global(&'s str),
+    /// `composes: a from "./file.css"`, `@value a from "./file.css"` and the
+    /// aliases of `:import("./file.css")` used in `:export`, left unresolved,
+    /// `from` is quoted as in the input.
+    Import { name: &'s str, from: &'s str },
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Export<'s> {
+    /// The original name, e.g. `a` for `.a`, `--a` for `--a`.
+    pub name: Cow<'s, str>,
+    pub values: Vec<ExportName<'s>>,
+}
+
+/// Collects the exports of a css module from its dependencies, in the order
+/// they first appear, with `composes` resolved into the final list of names.
+/// The local names exported are the names of classes, ids, keyframes and
+/// custom properties.
+#[derive(Debug)]
+pub struct ModuleExports<'s, N> {
+    handle_local_name: N,
+    exports: Vec<Export<'s>>,
+    indexes: HashMap<Cow<'s, str>, usize>,
+    values: HashMap<&'s str, &'s str>,
+    // The aliases of `:import` with the names and paths they import
+    imports: HashMap<&'s str, (&'s str, &'s str)>,
+    // The path of the last `:import`
+    import_path: Option<&'s str>,
+}
+
+impl<'s, N: HandleLocalName<'s>> ModuleExports<'s, N> {
+    pub fn new(handle_local_name: N) -> Self {
+        Self {
+            handle_local_name,
+            exports: Vec::new(),
+            indexes: HashMap::new(),
+            values: HashMap::new(),
+            imports: HashMap::new(),
+            import_path: None,
+        }
+    }
+
+    pub fn add(&mut self, dependency: &Dependency<'s>) {
+        match dependency {
+            Dependency::Composes {
+                local_classes,
+                names,
+                from,
+                range,
+            } => {
+                for &name in names {
+                    let values = match *from {
+                        Some("global") => vec![ExportName::Global(name)],
+                        Some(from) => vec![ExportName::Import { name, from }],
+                        // Composing a class of this module also composes what it composes
+                        None => match self.get(name) {
+                            Some(values) => values.to_vec(),
+                            None => vec![ExportName::Local(
+                                self.handle_local_name.handle_local_name(&LocalName {
+                                    kind: LocalKind::Class,
+                                    name,
+                                    range: range.clone(),
+                                    from: None,
+                                }),
+                            )],
+                        },
+                    };
+                    for &local_class in local_classes {
+                        for value in &values {
+                            self.insert(Cow::Borrowed(local_class), value.clone());
+                        }
+                    }
+                }
+            }
+            Dependency::ICSSImportFrom { path } => {
+                self.import_path = Some(path);
+            }
+            Dependency::ICSSImportValue { prop, value } => {
+                if let Some(path) = self.import_path {
+                    self.imports.insert(prop, (value, path));
+                }
+            }
+            Dependency::ICSSExportValue { prop, value } => {
+                let value = match self.imports.get(value) {
+                    Some(&(name, from)) => ExportName::Import { name, from },
+                    None => ExportName::Global(value),
+                };
+                self.insert(Cow::Borrowed(prop), value);
+            }
+            Dependency::ValueDecl { name, value } => {
+                let value = self.values.get(value).copied().unwrap_or(value);
+                self.values.insert(name, value);
+                self.insert(Cow::Borrowed(name), ExportName::Global(value));
+            }
+            Dependency::ValueImport { name, alias, from } => {
+                self.insert(Cow::Borrowed(alias), ExportName::Import { name, from });
+            }
+            dependency => {
+                let Some(local_name) = dependency.local_name() else {
+                    return;
+                };
+                // Defined in another module
+                if local_name.from.is_some() {
+                    return;
+                }
+                // Only classes, ids, keyframes and custom properties are
+                // exported, as css-loader does, so the other kinds can't
+                // merge into an export of the same name
+                let name = match local_name.kind {
+                    LocalKind::Class | LocalKind::Id | LocalKind::Keyframes => {
+                        Cow::Borrowed(local_name.name)
+                    }
+                    LocalKind::Var => Cow::Owned(format!("--{}", local_name.name)),
+                    _ => return,
+                };
+                let new_name = self.handle_local_name.handle_local_name(&local_name);
+                let new_name = match local_name.kind {
+                    LocalKind::Var => format!("--{new_name}"),
+                    _ => new_name,
+                };
+                self.insert(name, ExportName::Local(new_name));
+            }
+        }
+    }
+
+    fn insert(&mut self, name: Cow<'s, str>, value: ExportName<'s>) {
+        let index = match self.indexes.get(&name) {
+            Some(&index) => index,
+            None => {
+                self.indexes.insert(name.clone(), self.exports.len());
+                self.exports.push(Export {
+                    name,
+                    values: Vec::new(),
+                });
+                self.exports.len() - 1
+            }
+        };
+        let values = &mut self.exports[index].values;
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+}
+
+impl<'s, N> ModuleExports<'s, N> {
+    pub fn get(&self, name: &str) -> Option<&[ExportName<'s>]> {
+        let index = *self.indexes.get(name)?;
+        Some(&self.exports[index].values)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Export<'s>> {
+        self.exports.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.exports.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exports.is_empty()
+    }
+
+    pub fn into_exports(self) -> Vec<Export<'s>> {
+        self.exports
+    }
+}
+
+impl<'s, N: HandleLocalName<'s>> HandleDependency<'s> for ModuleExports<'s, N> {
+    fn handle_dependency(&mut self, dependency: Dependency<'s>) {
+        self.add(&dependency);
+    }
+}
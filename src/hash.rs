@@ -0,0 +1,215 @@
+/// MD4 (RFC 1320), the default hash function of css-loader's `localIdentHashFunction`.
+#[derive(Debug, Clone)]
+pub(crate) struct Md4 {
+    state: [u32; 4],
+    buffer: [u8; 64],
+    buffer_len: usize,
+    len: u64,
+}
+
+impl Default for Md4 {
+    fn default() -> Self {
+        Self {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            buffer: [0; 64],
+            buffer_len: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Md4 {
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        if self.buffer_len > 0 {
+            let n = (64 - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + n].copy_from_slice(&data[..n]);
+            self.buffer_len += n;
+            data = &data[n..];
+            if self.buffer_len < 64 {
+                return;
+            }
+            let block = self.buffer;
+            self.process(&block);
+            self.buffer_len = 0;
+        }
+        while data.len() >= 64 {
+            self.process(data[..64].try_into().unwrap());
+            data = &data[64..];
+        }
+        self.buffer[..data.len()].copy_from_slice(data);
+        self.buffer_len = data.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; 16] {
+        let bit_len = self.len.wrapping_mul(8);
+        let padding_len = if self.buffer_len < 56 {
+            56 - self.buffer_len
+        } else {
+            120 - self.buffer_len
+        };
+        let mut padding = [0u8; 64];
+        padding[0] = 0x80;
+        self.update(&padding[..padding_len]);
+        self.update(&bit_len.to_le_bytes());
+        let mut digest = [0; 16];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+
+    fn process(&mut self, block: &[u8; 64]) {
+        let mut x = [0u32; 16];
+        for (word, chunk) in x.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = self.state;
+
+        let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+        let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+        let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+        for &i in &[0, 4, 8, 12] {
+            a = a.wrapping_add(f(b, c, d)).wrapping_add(x[i]).rotate_left(3);
+            d = d
+                .wrapping_add(f(a, b, c))
+                .wrapping_add(x[i + 1])
+                .rotate_left(7);
+            c = c
+                .wrapping_add(f(d, a, b))
+                .wrapping_add(x[i + 2])
+                .rotate_left(11);
+            b = b
+                .wrapping_add(f(c, d, a))
+                .wrapping_add(x[i + 3])
+                .rotate_left(19);
+        }
+        for &i in &[0, 1, 2, 3] {
+            a = a
+                .wrapping_add(g(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(0x5a827999)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(g(a, b, c))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(0x5a827999)
+                .rotate_left(5);
+            c = c
+                .wrapping_add(g(d, a, b))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(0x5a827999)
+                .rotate_left(9);
+            b = b
+                .wrapping_add(g(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(0x5a827999)
+                .rotate_left(13);
+        }
+        for &i in &[0, 2, 1, 3] {
+            a = a
+                .wrapping_add(h(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(0x6ed9eba1)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(h(a, b, c))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(0x6ed9eba1)
+                .rotate_left(9);
+            c = c
+                .wrapping_add(h(d, a, b))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(0x6ed9eba1)
+                .rotate_left(11);
+            b = b
+                .wrapping_add(h(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(0x6ed9eba1)
+                .rotate_left(15);
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    const CHARS: &[u8; 16] = b"0123456789abcdef";
+    let mut result = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        result.push(CHARS[(byte >> 4) as usize] as char);
+        result.push(CHARS[(byte & 0xf) as usize] as char);
+    }
+    result
+}
+
+pub(crate) fn to_base64(bytes: &[u8], url_safe: bool) -> String {
+    let chars: &[u8; 64] = if url_safe {
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+    } else {
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+    };
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - i * 8));
+        for i in 0..=chunk.len() {
+            result.push(chars[(n >> (18 - i * 6)) as usize & 0b111111] as char);
+        }
+        if !url_safe {
+            for _ in chunk.len()..3 {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn md4_hex(input: &str) -> String {
+        let mut md4 = Md4::default();
+        md4.update(input.as_bytes());
+        to_hex(&md4.finalize())
+    }
+
+    #[test]
+    fn md4() {
+        assert_eq!(md4_hex(""), "31d6cfe0d16ae931b73c59d7e0c089c0");
+        assert_eq!(md4_hex("a"), "bde52cb31de33e46245e05fbdbd6fb24");
+        assert_eq!(md4_hex("abc"), "a448017aaf21d8525fc10ae87aa6729d");
+        assert_eq!(
+            md4_hex("message digest"),
+            "d9130a8164549fe818874806e1c7014b"
+        );
+        assert_eq!(
+            md4_hex("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789"),
+            "043f8582f241db351ce627e153e7f0e4"
+        );
+        assert_eq!(
+            md4_hex(
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            "e33b4ddc9c38f2199c3e7b164fcc0536"
+        );
+        assert_eq!(md4_hex(&"a".repeat(56)), "d5f9a9e9257077a5f08b0b92f348b0ad");
+        assert_eq!(md4_hex(&"a".repeat(64)), "52f5076fabd22680234a3fa9f9dc5732");
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(to_base64(b"", false), "");
+        assert_eq!(to_base64(b"f", false), "Zg==");
+        assert_eq!(to_base64(b"fo", false), "Zm8=");
+        assert_eq!(to_base64(b"foo", false), "Zm9v");
+        assert_eq!(to_base64(&[0xfb, 0xff], true), "-_8");
+    }
+}
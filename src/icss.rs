@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::Dependency;
+use crate::Range;
+use crate::Warning;
+use crate::WarningKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateMarker {
+    Temporary,
+    Permanent,
+}
+
+#[derive(Debug, Clone)]
+struct Import<'s> {
+    path: &'s str,
+    // Where the path is first imported
+    range: Range,
+    // The names imported from the path with their aliases
+    names: Vec<(&'s str, String)>,
+    // The paths imported before this one in the same rule
+    children: Vec<&'s str>,
+}
+
+/// The `:import` blocks of a css module, ordered the same way as
+/// postcss-modules-extract-imports: the imports of a rule keep their order,
+/// and imports of different rules are ordered by a topological sort.
+#[derive(Debug, Default)]
+pub(crate) struct IcssImports<'s> {
+    imports: Vec<Import<'s>>,
+    indexes: HashMap<&'s str, usize>,
+    visited: HashSet<(u32, &'s str)>,
+    siblings: HashMap<u32, Vec<&'s str>>,
+    rule_index: u32,
+    postfix: usize,
+    // The path of the last `:import`
+    import_path: Option<&'s str>,
+}
+
+impl<'s> IcssImports<'s> {
+    /// Adds the imports of `composes`, `var()`, `:import` and `@value`.
+    pub fn add_dependency(&mut self, input: &'s str, dependency: &Dependency<'s>) {
+        match *dependency {
+            Dependency::LocalClass { .. } | Dependency::LocalId { .. } => {
+                self.rule_index += 1;
+            }
+            Dependency::Composes {
+                ref names,
+                from: Some(from),
+                ref range,
+                ..
+            } if from != "global" => {
+                self.add(from, range.clone());
+                for name in names {
+                    self.alias(from, name);
+                }
+            }
+            Dependency::LocalVar {
+                ref range,
+                from: Some(from),
+                ..
+            } => {
+                self.add(from, range.clone());
+                self.alias(from, &input[range.start as usize..range.end as usize]);
+            }
+            Dependency::ICSSImportFrom { path } => {
+                self.add(path, Range::new(0, 0));
+                self.import_path = Some(path);
+            }
+            Dependency::ICSSImportValue { prop, value } => {
+                if let Some(path) = self.import_path {
+                    self.insert(path, value, prop);
+                }
+            }
+            Dependency::ValueImport { name, alias, from } => {
+                self.add(from, Range::new(0, 0));
+                self.insert(from, name, alias);
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds `path` to the imports of the current rule.
+    fn add(&mut self, path: &'s str, range: Range) {
+        let path = unquote(path);
+        let index = match self.indexes.get(path) {
+            Some(&index) => index,
+            None => {
+                self.indexes.insert(path, self.imports.len());
+                self.imports.push(Import {
+                    path,
+                    range,
+                    names: Vec::new(),
+                    children: Vec::new(),
+                });
+                self.imports.len() - 1
+            }
+        };
+        if !self.visited.insert((self.rule_index, path)) {
+            return;
+        }
+        let siblings = self.siblings.entry(self.rule_index).or_default();
+        self.imports[index].children.extend(siblings.iter());
+        siblings.push(path);
+    }
+
+    /// The alias of `name` imported from `path`, `path` must be added before.
+    pub fn alias(&mut self, path: &'s str, name: &'s str) -> &str {
+        let import = &mut self.imports[self.indexes[unquote(path)]];
+        let index = match import.names.iter().position(|(n, _)| *n == name) {
+            Some(index) => index,
+            None => {
+                // Custom properties keep the `--` prefix, e.g. `--i__imported_x_0`
+                let (prefix, unprefixed) = match name.strip_prefix("--") {
+                    Some(unprefixed) => ("--", unprefixed),
+                    None => ("", name),
+                };
+                let alias = format!(
+                    "{prefix}i__imported_{}_{}",
+                    unprefixed.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_"),
+                    self.postfix
+                );
+                self.postfix += 1;
+                import.names.push((name, alias));
+                import.names.len() - 1
+            }
+        };
+        &import.names[index].1
+    }
+
+    /// Adds an existing `alias: name` of `:import` and `@value`.
+    fn insert(&mut self, path: &'s str, name: &'s str, alias: &'s str) {
+        let import = &mut self.imports[self.indexes[unquote(path)]];
+        if !import.names.iter().any(|(n, _)| *n == name) {
+            import.names.push((name, alias.to_string()));
+        }
+    }
+
+    pub fn write(
+        &self,
+        output: &mut impl fmt::Write,
+        warnings: &mut Vec<Warning<'s>>,
+    ) -> fmt::Result {
+        for import in self.sorted(warnings) {
+            writeln!(output, ":import(\"{}\") {{", import.path)?;
+            for (name, alias) in &import.names {
+                writeln!(output, "    {alias}: {name};")?;
+            }
+            output.write_str("}\n")?;
+        }
+        Ok(())
+    }
+
+    /// The imported paths, unquoted, with where they are first imported.
+    pub fn order(&self, warnings: &mut Vec<Warning<'s>>) -> Vec<(&'s str, Range)> {
+        self.sorted(warnings)
+            .into_iter()
+            .map(|import| (import.path, import.range.clone()))
+            .collect()
+    }
+
+    fn sorted(&self, warnings: &mut Vec<Warning<'s>>) -> Vec<&Import<'s>> {
+        let mut order = Vec::with_capacity(self.imports.len());
+        let mut state = HashMap::new();
+        for import in &self.imports {
+            self.walk(import, &mut state, &mut order, warnings);
+        }
+        order
+    }
+
+    fn walk<'a>(
+        &'a self,
+        import: &'a Import<'s>,
+        state: &mut HashMap<&'s str, StateMarker>,
+        order: &mut Vec<&'a Import<'s>>,
+        warnings: &mut Vec<Warning<'s>>,
+    ) {
+        match state.get(import.path) {
+            Some(StateMarker::Permanent) => return,
+            Some(StateMarker::Temporary) => {
+                warnings.push(Warning::new(
+                    import.range.clone(),
+                    WarningKind::Unexpected {
+                        message: "Failed to resolve order of composed modules",
+                    },
+                ));
+                return;
+            }
+            None => {}
+        }
+        state.insert(import.path, StateMarker::Temporary);
+        for child in &import.children {
+            self.walk(&self.imports[self.indexes[child]], state, order, warnings);
+        }
+        state.insert(import.path, StateMarker::Permanent);
+        order.push(import);
+    }
+}
+
+fn unquote(path: &str) -> &str {
+    path.trim_matches(|c| c == '\'' || c == '"')
+}
@@ -0,0 +1,478 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::Export;
+use crate::ExportName;
+
+/// How the exports are written in JavaScript.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum JsExportsFormat {
+    /// `export var a = "...";`, `default` is exported as `_default`.
+    #[default]
+    Named,
+    /// `export default { a: "..." };`
+    Default,
+    /// `module.exports = { a: "..." };`
+    CommonJs,
+}
+
+/// Same as css-loader's `exportLocalsConvention`.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ExportLocalsConvention {
+    /// `a-b` is exported as is.
+    #[default]
+    AsIs,
+    /// `a-b_c` is exported as `a-b_c` and `aBC`.
+    CamelCase,
+    /// `a-b_c` is exported as `aBC` only.
+    CamelCaseOnly,
+    /// `a-b_c` is exported as `a-b_c` and `aB_c`.
+    Dashes,
+    /// `a-b_c` is exported as `aB_c` only.
+    DashesOnly,
+}
+
+impl ExportLocalsConvention {
+    fn names<'a>(&self, name: &'a str) -> (Option<&'a str>, Option<String>) {
+        match self {
+            ExportLocalsConvention::AsIs => (Some(name), None),
+            ExportLocalsConvention::CamelCase => (Some(name), Some(camel_case(name))),
+            ExportLocalsConvention::CamelCaseOnly => (None, Some(camel_case(name))),
+            ExportLocalsConvention::Dashes => (Some(name), Some(dashes_camel_case(name))),
+            ExportLocalsConvention::DashesOnly => (None, Some(dashes_camel_case(name))),
+        }
+    }
+
+    /// The name another module, written with the same convention, exports `name` as.
+    fn import_name(&self, name: &str) -> String {
+        match self {
+            ExportLocalsConvention::CamelCaseOnly => camel_case(name),
+            ExportLocalsConvention::DashesOnly => dashes_camel_case(name),
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// Writes the exports of a css module as a JavaScript module, composes from
+/// other files are imported from them.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct JsExports {
+    format: JsExportsFormat,
+    export_locals_convention: ExportLocalsConvention,
+}
+
+impl JsExports {
+    pub fn new(format: JsExportsFormat) -> Self {
+        Self {
+            format,
+            export_locals_convention: ExportLocalsConvention::default(),
+        }
+    }
+
+    pub fn with_export_locals_convention(
+        mut self,
+        export_locals_convention: ExportLocalsConvention,
+    ) -> Self {
+        self.export_locals_convention = export_locals_convention;
+        self
+    }
+
+    pub fn write<'a, 's: 'a>(
+        &self,
+        exports: impl IntoIterator<Item = &'a Export<'s>>,
+        output: &mut impl fmt::Write,
+    ) -> fmt::Result {
+        let exports: Vec<_> = exports.into_iter().collect();
+
+        let mut imports: Vec<&str> = Vec::new();
+        for export in &exports {
+            for value in &export.values {
+                if let ExportName::Import { from, .. } = value {
+                    if !imports.contains(from) {
+                        imports.push(from);
+                    }
+                }
+            }
+        }
+        for (index, from) in imports.iter().enumerate() {
+            let request = JsString(unquote(from));
+            match self.format {
+                JsExportsFormat::Named => {
+                    writeln!(output, "import * as {} from {request};", ImportId(index))?
+                }
+                JsExportsFormat::Default => {
+                    writeln!(output, "import {} from {request};", ImportId(index))?
+                }
+                JsExportsFormat::CommonJs => {
+                    writeln!(output, "var {} = require({request});", ImportId(index))?
+                }
+            }
+        }
+
+        let locals: Vec<_> = self
+            .locals(&exports)
+            .into_iter()
+            .map(|(name, export)| (name, self.value(&export.values, &imports)))
+            .collect();
+
+        match self.format {
+            JsExportsFormat::Named => {
+                for (index, (name, value)) in locals.iter().enumerate() {
+                    if is_identifier(name) && !is_reserved_word(name) {
+                        writeln!(output, "export var {name} = {value};")?;
+                    } else {
+                        writeln!(output, "var __css_export_{index}__ = {value};")?;
+                        writeln!(
+                            output,
+                            "export {{ __css_export_{index}__ as {} }};",
+                            JsString(name)
+                        )?;
+                    }
+                }
+            }
+            JsExportsFormat::Default | JsExportsFormat::CommonJs => {
+                if self.format == JsExportsFormat::Default {
+                    output.write_str("export default {")?;
+                } else {
+                    output.write_str("module.exports = {")?;
+                }
+                if !locals.is_empty() {
+                    output.write_char('\n')?;
+                }
+                for (name, value) in &locals {
+                    if is_identifier(name) {
+                        writeln!(output, "  {name}: {value},")?;
+                    } else {
+                        writeln!(output, "  {}: {value},", JsString(name))?;
+                    }
+                }
+                output.write_str("};\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the TypeScript declaration of what `write` writes, names are
+    /// sorted so the output only changes when the names do.
+    pub fn write_dts<'a, 's: 'a>(
+        &self,
+        exports: impl IntoIterator<Item = &'a Export<'s>>,
+        output: &mut impl fmt::Write,
+    ) -> fmt::Result {
+        let exports: Vec<_> = exports.into_iter().collect();
+        let mut names: Vec<_> = self
+            .locals(&exports)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort_unstable();
+
+        if self.format == JsExportsFormat::Named {
+            for (index, name) in names.iter().enumerate() {
+                if is_identifier(name) && !is_reserved_word(name) {
+                    writeln!(output, "export declare const {name}: string;")?;
+                } else {
+                    writeln!(output, "declare const __css_export_{index}__: string;")?;
+                    writeln!(
+                        output,
+                        "export {{ __css_export_{index}__ as {} }};",
+                        JsString(name)
+                    )?;
+                }
+            }
+            return Ok(());
+        }
+        output.write_str("declare const styles: {")?;
+        if !names.is_empty() {
+            output.write_char('\n')?;
+        }
+        for name in &names {
+            if is_identifier(name) {
+                writeln!(output, "  readonly {name}: string;")?;
+            } else {
+                writeln!(output, "  readonly {}: string;", JsString(name))?;
+            }
+        }
+        output.write_str("};\n")?;
+        if self.format == JsExportsFormat::Default {
+            output.write_str("export default styles;\n")
+        } else {
+            output.write_str("export = styles;\n")
+        }
+    }
+
+    /// The exported names with the export they stand for.
+    fn locals<'a, 's>(&self, exports: &[&'a Export<'s>]) -> Vec<(String, &'a Export<'s>)> {
+        let mut names = HashSet::new();
+        let mut locals = Vec::new();
+        for &export in exports {
+            let (name, converted) = self.export_locals_convention.names(&export.name);
+            for name in name.into_iter().chain(converted.as_deref()) {
+                let name = self.export_name(name);
+                // `a-b` and `aB` are both `aB` in camel case, the first one wins
+                if names.insert(name.clone()) {
+                    locals.push((name, export));
+                }
+            }
+        }
+        locals
+    }
+
+    /// A named `default` export would be the default export of the module,
+    /// it's exported as `_default` like css-loader does.
+    fn export_name(&self, name: &str) -> String {
+        if self.format == JsExportsFormat::Named && name == "default" {
+            format!("_{name}")
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// The names of an export joined by spaces, as a JavaScript expression.
+    fn value(&self, values: &[ExportName], imports: &[&str]) -> String {
+        let mut terms = Vec::new();
+        let mut literal = String::new();
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                literal.push(' ');
+            }
+            match value {
+                ExportName::Local(name) => literal += name,
+                ExportName::Global(name) => literal += name,
+                ExportName::Import { name, from } => {
+                    if !literal.is_empty() {
+                        terms.push(JsString(&literal).to_string());
+                        literal.clear();
+                    }
+                    let index = imports.iter().position(|import| import == from).unwrap();
+                    let name = self.export_name(&self.export_locals_convention.import_name(name));
+                    terms.push(format!("{}[{}]", ImportId(index), JsString(&name)));
+                }
+            }
+        }
+        if !literal.is_empty() || terms.is_empty() {
+            terms.push(JsString(&literal).to_string());
+        }
+        terms.join(" + ")
+    }
+}
+
+struct ImportId(usize);
+
+impl fmt::Display for ImportId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "__css_import_{}__", self.0)
+    }
+}
+
+/// A double quoted JavaScript string literal.
+struct JsString<'a>(&'a str);
+
+impl fmt::Display for JsString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Write::write_char(f, '"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                '\u{0}'..='\u{1f}' | '\u{2028}' | '\u{2029}' => write!(f, "\\u{:04x}", c as u32)?,
+                c => fmt::Write::write_char(f, c)?,
+            }
+        }
+        fmt::Write::write_char(f, '"')
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    let quoted = s.len() >= 2
+        && (s.starts_with('"') && s.ends_with('"') || s.starts_with('\'') && s.ends_with('\''));
+    if quoted {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn is_reserved_word(name: &str) -> bool {
+    matches!(
+        name,
+        "await"
+            | "break"
+            | "case"
+            | "catch"
+            | "class"
+            | "const"
+            | "continue"
+            | "debugger"
+            | "default"
+            | "delete"
+            | "do"
+            | "else"
+            | "enum"
+            | "export"
+            | "extends"
+            | "false"
+            | "finally"
+            | "for"
+            | "function"
+            | "if"
+            | "implements"
+            | "import"
+            | "in"
+            | "instanceof"
+            | "interface"
+            | "let"
+            | "new"
+            | "null"
+            | "package"
+            | "private"
+            | "protected"
+            | "public"
+            | "return"
+            | "static"
+            | "super"
+            | "switch"
+            | "this"
+            | "throw"
+            | "true"
+            | "try"
+            | "typeof"
+            | "var"
+            | "void"
+            | "while"
+            | "with"
+            | "yield"
+            | "arguments"
+            | "eval"
+    )
+}
+
+/// Same as css-loader's `dashesCamelCase`, `a--b` is `aB`.
+fn dashes_camel_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '-' {
+            result.push(c);
+            continue;
+        }
+        let mut dashes = 1;
+        while chars.next_if_eq(&'-').is_some() {
+            dashes += 1;
+        }
+        match chars.peek() {
+            Some(&next) if next.is_ascii_alphanumeric() || next == '_' => {
+                result.push(next.to_ascii_uppercase());
+                chars.next();
+            }
+            _ => result.extend(std::iter::repeat('-').take(dashes)),
+        }
+    }
+    result
+}
+
+/// Same as css-loader's `camelCase`, `a-b_c` is `aBC`, `FooBar` is `fooBar`.
+fn camel_case(name: &str) -> String {
+    let name = name.trim();
+    let mut chars: Vec<char> = name.chars().collect();
+    if chars.len() <= 1 {
+        return name.to_lowercase();
+    }
+    if name != name.to_lowercase() {
+        chars = preserve_camel_case(&chars);
+    }
+    let is_separator = |c: char| matches!(c, '_' | '.' | '-' | ' ');
+    let start = chars
+        .iter()
+        .position(|&c| !is_separator(c))
+        .unwrap_or(chars.len());
+    let chars: Vec<char> = chars[start..]
+        .iter()
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    // `[_.\- ]+(\w|$)` is the uppercase `\w`
+    let mut words = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_separator(chars[i]) {
+            words.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && is_separator(chars[i]) {
+            i += 1;
+        }
+        match chars.get(i) {
+            Some(&c) if is_word(c) => {
+                words.extend(c.to_uppercase());
+                i += 1;
+            }
+            Some(_) => words.extend(&chars[start..i]),
+            None => {}
+        }
+    }
+    // `\d+\w` is uppercase
+    let mut result = String::with_capacity(words.len());
+    let mut i = 0;
+    while i < words.len() {
+        if !words[i].is_ascii_digit() {
+            result.push(words[i]);
+            i += 1;
+            continue;
+        }
+        while i < words.len() && words[i].is_ascii_digit() {
+            result.push(words[i]);
+            i += 1;
+        }
+        if let Some(&c) = words.get(i) {
+            if is_word(c) {
+                result.extend(c.to_uppercase());
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Inserts `-` between words of camel case, `fooBar` is `foo-Bar`, `FOOBar` is `FOO-Bar`.
+fn preserve_camel_case(chars: &[char]) -> Vec<char> {
+    let mut result: Vec<char> = chars.to_vec();
+    let mut is_last_lower = false;
+    let mut is_last_upper = false;
+    let mut is_last_last_upper = false;
+    let mut i = 0;
+    while i < result.len() {
+        let c = result[i];
+        if is_last_lower && c.is_uppercase() {
+            result.insert(i, '-');
+            is_last_lower = false;
+            is_last_last_upper = is_last_upper;
+            is_last_upper = true;
+            i += 1;
+        } else if is_last_upper && is_last_last_upper && c.is_lowercase() {
+            result.insert(i - 1, '-');
+            is_last_last_upper = is_last_upper;
+            is_last_upper = false;
+            is_last_lower = true;
+        } else {
+            is_last_lower = c.is_lowercase();
+            is_last_last_upper = is_last_upper;
+            is_last_upper = c.is_uppercase();
+        }
+        i += 1;
+    }
+    result
+}
@@ -75,6 +75,8 @@ pub trait Visitor<'s> {
     fn at_keyword(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()>;
     fn left_curly_bracket(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()>;
     fn right_curly_bracket(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()>;
+    fn left_square_bracket(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()>;
+    fn right_square_bracket(&mut self, lexer: &mut Lexer<'s>, start: Pos, end: Pos) -> Option<()>;
 }
 
 #[derive(Debug, Clone)]
@@ -189,9 +191,9 @@ impl<'s> Lexer<'s> {
                 C_SEMICOLON => self.consume_semicolon(visitor)?,
                 C_LESS_THAN_SIGN => self.consume_less_than_sign()?,
                 C_AT_SIGN => self.consume_at_sign(visitor)?,
-                C_LEFT_SQUARE => self.consume_delim(),
+                C_LEFT_SQUARE => self.consume_left_square(visitor)?,
                 C_REVERSE_SOLIDUS => self.consume_reverse_solidus(visitor)?,
-                C_RIGHT_SQUARE => self.consume_delim(),
+                C_RIGHT_SQUARE => self.consume_right_square(visitor)?,
                 C_LEFT_CURLY => self.consume_left_curly(visitor)?,
                 C_RIGHT_CURLY => self.consume_right_curly(visitor)?,
                 c if is_digit(c) => self.consume_numeric_token()?,
@@ -516,6 +518,18 @@ impl<'s> Lexer<'s> {
         let end = self.cur_pos()?;
         visitor.right_curly_bracket(self, end - 1, end)
     }
+
+    pub fn consume_left_square<T: Visitor<'s>>(&mut self, visitor: &mut T) -> Option<()> {
+        self.consume();
+        let end = self.cur_pos()?;
+        visitor.left_square_bracket(self, end - 1, end)
+    }
+
+    pub fn consume_right_square<T: Visitor<'s>>(&mut self, visitor: &mut T) -> Option<()> {
+        self.consume();
+        let end = self.cur_pos()?;
+        visitor.right_square_bracket(self, end - 1, end)
+    }
 }
 
 impl<'s, I: Iterator<Item = char>> Lexer<'s, I> {
@@ -737,6 +751,16 @@ mod tests {
             self.add("right_curly", lexer.slice(start, end)?);
             Some(())
         }
+
+        fn left_square_bracket(&mut self, lexer: &mut Lexer, start: Pos, end: Pos) -> Option<()> {
+            self.add("left_square", lexer.slice(start, end)?);
+            Some(())
+        }
+
+        fn right_square_bracket(&mut self, lexer: &mut Lexer, start: Pos, end: Pos) -> Option<()> {
+            self.add("right_square", lexer.slice(start, end)?);
+            Some(())
+        }
     }
 
     fn assert_lexer_snapshot(input: &str, snapshot: &str) {
@@ -1,16 +1,49 @@
+mod bundle;
 mod dependencies;
+mod exports;
+mod hash;
+mod icss;
+mod js_exports;
 mod lexer;
+mod local_ident_name;
+mod module_graph;
+mod short_names;
+mod source_map;
+mod transform;
+mod tree_shaking;
+mod url;
 
+pub use bundle::Bundler;
 pub use dependencies::Dependency;
 pub use dependencies::LexDependencies;
+pub use dependencies::LocalKind;
+pub use dependencies::LocalName;
+pub use dependencies::LocalScope;
 pub use dependencies::Mode;
 pub use dependencies::ModeData;
 pub use dependencies::Range;
 pub use dependencies::UrlRangeKind;
 pub use dependencies::Warning;
 pub use dependencies::WarningKind;
+pub use exports::Export;
+pub use exports::ExportName;
+pub use exports::ModuleExports;
+pub use js_exports::ExportLocalsConvention;
+pub use js_exports::JsExports;
+pub use js_exports::JsExportsFormat;
 pub use lexer::Lexer;
 pub use lexer::Pos;
+pub use local_ident_name::LocalIdentName;
+pub use module_graph::Module;
+pub use module_graph::ModuleGraph;
+pub use module_graph::ModuleWarning;
+pub use short_names::ShortNames;
+pub use source_map::InputSourceMap;
+pub use source_map::OriginalPosition;
+pub use source_map::OriginalRange;
+pub use source_map::SourceMap;
+pub use transform::Transformer;
+pub use url::UrlRewriter;
 
 pub trait HandleDependency<'s> {
     fn handle_dependency(&mut self, dependency: Dependency<'s>);
@@ -20,6 +53,28 @@ pub trait HandleWarning<'s> {
     fn handle_warning(&mut self, warning: Warning<'s>);
 }
 
+pub trait HandleLocalName<'s> {
+    fn handle_local_name(&mut self, local_name: &LocalName<'s>) -> String;
+}
+
+pub trait ResolveModule {
+    /// The path of the module `request` refers to, `request` is imported by the module at `path`.
+    fn resolve_module(&mut self, path: &str, request: &str) -> Option<String>;
+}
+
+pub trait LoadModule {
+    fn load_module(&mut self, path: &str) -> Option<String>;
+}
+
+pub trait RewriteUrl {
+    /// The new URL of `request`, `None` keeps it unchanged.
+    fn rewrite_url(&mut self, request: &str) -> Option<String>;
+}
+
+pub trait HandleModuleLocalName {
+    fn handle_module_local_name(&mut self, path: &str, local_name: &LocalName) -> String;
+}
+
 impl<'s, F: FnMut(Dependency<'s>)> HandleDependency<'s> for F {
     fn handle_dependency(&mut self, dependency: Dependency<'s>) {
         self(dependency);
@@ -32,6 +87,36 @@ impl<'s, F: FnMut(Warning<'s>)> HandleWarning<'s> for F {
     }
 }
 
+impl<'s, F: FnMut(&LocalName<'s>) -> String> HandleLocalName<'s> for F {
+    fn handle_local_name(&mut self, local_name: &LocalName<'s>) -> String {
+        self(local_name)
+    }
+}
+
+impl<F: FnMut(&str, &str) -> Option<String>> ResolveModule for F {
+    fn resolve_module(&mut self, path: &str, request: &str) -> Option<String> {
+        self(path, request)
+    }
+}
+
+impl<F: FnMut(&str) -> Option<String>> LoadModule for F {
+    fn load_module(&mut self, path: &str) -> Option<String> {
+        self(path)
+    }
+}
+
+impl<F: FnMut(&str) -> Option<String>> RewriteUrl for F {
+    fn rewrite_url(&mut self, request: &str) -> Option<String> {
+        self(request)
+    }
+}
+
+impl<F: FnMut(&str, &LocalName) -> String> HandleModuleLocalName for F {
+    fn handle_module_local_name(&mut self, path: &str, local_name: &LocalName) -> String {
+        self(path, local_name)
+    }
+}
+
 pub fn lex_dependencies<'s>(
     input: &'s str,
     mode: Mode,
@@ -43,6 +128,31 @@ pub fn lex_dependencies<'s>(
     lexer.lex(&mut visitor);
 }
 
+/// Same as `lex_dependencies`, the ranges are also translated to the sources of `source_map`.
+pub fn lex_dependencies_with_source_map<'s, 'm>(
+    input: &'s str,
+    mode: Mode,
+    source_map: &'m SourceMap,
+    mut handle_dependency: impl FnMut(Dependency<'s>, Option<OriginalRange<'m>>),
+    mut handle_warning: impl FnMut(Warning<'s>, Option<OriginalRange<'m>>),
+) {
+    let source_map = InputSourceMap::new(source_map, input);
+    lex_dependencies(
+        input,
+        mode,
+        |dependency: Dependency<'s>| {
+            let original = dependency
+                .range()
+                .and_then(|range| source_map.original_range(range));
+            handle_dependency(dependency, original);
+        },
+        |warning: Warning<'s>| {
+            let original = source_map.original_range(warning.range());
+            handle_warning(warning, original);
+        },
+    );
+}
+
 pub fn collect_dependencies(input: &str, mode: Mode) -> (Vec<Dependency>, Vec<Warning>) {
     let mut dependencies = Vec::new();
     let mut warnings = Vec::new();
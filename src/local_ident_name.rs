@@ -0,0 +1,203 @@
+use crate::hash::to_base64;
+use crate::hash::to_hex;
+use crate::hash::Md4;
+
+/// Generates local identifiers from a webpack style template, e.g.
+/// `[path][name]__[local]` or `[hash:base64:5]`, the same way as css-loader's
+/// `localIdentName`.
+///
+/// Supported placeholders are `[name]`, `[local]`, `[path]`, `[folder]`,
+/// `[file]`, `[ext]`, `[hash]`, `[contenthash]` and `[fullhash]`, hashes
+/// accept a digest and a length, e.g. `[hash:base64:5]` or `[md4:hash:hex:8]`.
+/// The hash is always MD4, so names are stable across machines.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct LocalIdentName {
+    template: String,
+    context: String,
+    hash_salt: Option<String>,
+}
+
+impl LocalIdentName {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+            context: String::new(),
+            hash_salt: None,
+        }
+    }
+
+    /// Resource paths are made relative to `context` for `[path]` and hashing.
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = context.into();
+        self
+    }
+
+    /// Prepended to the hashed content.
+    pub fn with_hash_salt(mut self, hash_salt: impl Into<String>) -> Self {
+        self.hash_salt = Some(hash_salt.into());
+        self
+    }
+
+    pub fn generate(&self, resource_path: &str, local: &str) -> String {
+        let relative_path = relative_path(&self.context, resource_path);
+        let (path, file) = relative_path
+            .rfind('/')
+            .map_or(("", relative_path.as_str()), |i| {
+                relative_path.split_at(i + 1)
+            });
+        let (name, ext) = match file.rfind('.') {
+            Some(i) if i > 0 => file.split_at(i),
+            _ => (file, ""),
+        };
+        let folder = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default();
+
+        let mut result = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('[') {
+            let Some(end) = rest[start..].find(']').map(|end| start + end) else {
+                break;
+            };
+            result += &rest[..start];
+            let placeholder = &rest[start + 1..end];
+            match placeholder.to_ascii_lowercase().as_str() {
+                "name" => result += name,
+                "path" => result += path,
+                "folder" => result += folder,
+                "file" => result += &relative_path,
+                "ext" => result += ext,
+                // Replaced after escaping
+                "local" => result += "[local]",
+                _ => match HashPlaceholder::parse(placeholder) {
+                    // Each placeholder has its own digest and length
+                    Some(placeholder) => result += &self.hash(&relative_path, local, &placeholder),
+                    None => result += &rest[start..=end],
+                },
+            }
+            rest = &rest[end + 1..];
+        }
+        result += rest;
+        escape_local_ident(&result).replace("\\[local\\]", local)
+    }
+
+    fn hash(&self, relative_path: &str, local: &str, placeholder: &HashPlaceholder) -> String {
+        let mut hash = String::new();
+        let mut tier: u32 = 0;
+        while hash.len() < placeholder.length {
+            let mut md4 = Md4::default();
+            if let Some(salt) = &self.hash_salt {
+                md4.update(salt.as_bytes());
+            }
+            md4.update(&tier.to_le_bytes());
+            md4.update(relative_path.as_bytes());
+            md4.update(b"\0");
+            md4.update(local.as_bytes());
+            let digest = md4.finalize();
+            hash += &match placeholder.digest.as_str() {
+                "base64" => to_base64(&digest, false),
+                "base64url" => to_base64(&digest, true),
+                _ => to_hex(&digest),
+            };
+            // Same as css-loader: no leading digits, only alphanumerics and underscores
+            hash = hash
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .chars()
+                .filter_map(|c| match c {
+                    '/' => Some('_'),
+                    c if c.is_ascii_alphanumeric() || c == '_' => Some(c),
+                    _ => None,
+                })
+                .collect();
+            tier += 1;
+        }
+        hash.truncate(placeholder.length);
+        hash
+    }
+}
+
+struct HashPlaceholder {
+    digest: String,
+    length: usize,
+}
+
+impl HashPlaceholder {
+    /// `[<function>:hash:<digest>:<length>]`, the function is ignored.
+    fn parse(placeholder: &str) -> Option<Self> {
+        let mut parts = placeholder.split(':').peekable();
+        if !matches!(parts.peek(), Some(part) if is_hash_name(part)) {
+            parts.next();
+        }
+        if !is_hash_name(parts.next()?) {
+            return None;
+        }
+        let mut digest = "hex".to_string();
+        let mut length = 20;
+        for part in parts {
+            if let Ok(n) = part.parse() {
+                length = n;
+            } else if part.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                digest = part.to_ascii_lowercase();
+            } else {
+                return None;
+            }
+        }
+        Some(Self { digest, length })
+    }
+}
+
+fn is_hash_name(name: &str) -> bool {
+    name.eq_ignore_ascii_case("hash")
+        || name.eq_ignore_ascii_case("contenthash")
+        || name.eq_ignore_ascii_case("fullhash")
+}
+
+fn relative_path(context: &str, path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let context = context.replace('\\', "/");
+    let context: Vec<_> = context.split('/').filter(|part| !part.is_empty()).collect();
+    let parts: Vec<_> = path.split('/').filter(|part| !part.is_empty()).collect();
+    let common = context
+        .iter()
+        .zip(&parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative: Vec<&str> = vec![".."; context.len() - common];
+    relative.extend(&parts[common..]);
+    relative.join("/")
+}
+
+/// Same as css-loader's `escapeLocalIdent`, which escapes with `cssesc`.
+fn escape_local_ident(ident: &str) -> String {
+    let mut result = String::with_capacity(ident.len());
+    let mut chars = ident.chars().peekable();
+    if let Some(&c) = chars.peek() {
+        let starts_with_digit = c.is_ascii_digit()
+            || c == '-'
+                && matches!(ident[1..].chars().next(), Some(c2) if c2.is_ascii_digit() || c2 == '-');
+        if starts_with_digit {
+            result.push('_');
+        }
+    }
+    for c in chars {
+        match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' | '.' => result.push('-'),
+            '\u{0}'..='\u{1f}' | '\u{80}'..='\u{9f}' => result.push('-'),
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() => {
+                result.push(c)
+            }
+            c => {
+                result.push('\\');
+                result.push(c);
+            }
+        }
+    }
+    // An identifier can't start with `--` or `-<digit>`
+    if result.starts_with('-') && result[1..].starts_with(|c: char| c == '-' || c.is_ascii_digit())
+    {
+        result.insert(0, '\\');
+    }
+    result
+}
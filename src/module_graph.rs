@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::icss::IcssImports;
+use crate::transform::offset_of;
+use crate::ExportName;
+use crate::HandleModuleLocalName;
+use crate::LexDependencies;
+use crate::Lexer;
+use crate::LoadModule;
+use crate::LocalName;
+use crate::LocalScope;
+use crate::Mode;
+use crate::ModuleExports;
+use crate::Range;
+use crate::ResolveModule;
+use crate::Warning;
+
+/// A warning of a module in a `ModuleGraph`, `range` is in the source of the module.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct ModuleWarning {
+    pub path: String,
+    pub range: Range,
+    pub message: String,
+}
+
+impl ModuleWarning {
+    fn new(path: &str, range: Range, message: String) -> Self {
+        Self {
+            path: path.to_string(),
+            range,
+            message,
+        }
+    }
+}
+
+impl Display for ModuleWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Module {
+    path: String,
+    source: String,
+    // Unquoted requests of `composes`, `var()`, `:import` and `@value`, with
+    // the modules they resolve to, in the order of their `:import` blocks
+    requests: Vec<(String, Option<usize>)>,
+    exports: Vec<(String, Vec<String>)>,
+}
+
+impl Module {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The indexes of the imported modules.
+    pub fn dependencies(&self) -> impl Iterator<Item = usize> + '_ {
+        self.requests.iter().filter_map(|(_, module)| *module)
+    }
+
+    /// The exports with the names imported from other modules resolved, in
+    /// the order they first appear.
+    pub fn exports(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.exports
+            .iter()
+            .map(|(name, values)| (name.as_str(), values.as_slice()))
+    }
+
+    pub fn export(&self, name: &str) -> Option<&[String]> {
+        self.exports
+            .iter()
+            .find(|(export, _)| export == name)
+            .map(|(_, values)| values.as_slice())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum ModuleState {
+    Loading,
+    Loaded,
+}
+
+/// The css modules reachable through `composes`, `var(--x from "...")`,
+/// `:import` and `@value` imports, loaded on demand by `add`.
+#[derive(Debug)]
+pub struct ModuleGraph<R, L, N> {
+    mode: Mode,
+    local_scope: LocalScope,
+    resolver: R,
+    loader: L,
+    handle_local_name: N,
+    modules: Vec<Module>,
+    states: Vec<ModuleState>,
+    indexes: HashMap<String, usize>,
+    warnings: Vec<ModuleWarning>,
+}
+
+impl<R: ResolveModule, L: LoadModule, N: HandleModuleLocalName> ModuleGraph<R, L, N> {
+    pub fn new(mode: Mode, resolver: R, loader: L, handle_local_name: N) -> Self {
+        Self {
+            mode,
+            local_scope: LocalScope::default(),
+            resolver,
+            loader,
+            handle_local_name,
+            modules: Vec::new(),
+            states: Vec::new(),
+            indexes: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_local_scope(mut self, local_scope: LocalScope) -> Self {
+        self.local_scope = local_scope;
+        self
+    }
+
+    /// Loads the module at `path` and the modules it imports, returns the
+    /// index of the module, `None` if it can't be loaded.
+    pub fn add(&mut self, path: &str) -> Option<usize> {
+        if let Some(&index) = self.indexes.get(path) {
+            return Some(index);
+        }
+        let source = self.loader.load_module(path)?;
+        let index = self.modules.len();
+        self.indexes.insert(path.to_string(), index);
+        self.modules.push(Module {
+            path: path.to_string(),
+            source: String::new(),
+            requests: Vec::new(),
+            exports: Vec::new(),
+        });
+        self.states.push(ModuleState::Loading);
+        self.load(index, &source);
+        self.modules[index].source = source;
+        self.states[index] = ModuleState::Loaded;
+        Some(index)
+    }
+
+    /// Lexes the source of a module, adds the modules it imports and resolves its exports.
+    fn load(&mut self, index: usize, source: &str) {
+        let path = self.modules[index].path.clone();
+        let path = path.as_str();
+        let mut dependencies = Vec::new();
+        let mut warnings = Vec::new();
+        let mut lexer = Lexer::new(source);
+        let mut visitor = LexDependencies::new(
+            |dependency| dependencies.push(dependency),
+            |warning| warnings.push(warning),
+            self.mode,
+        )
+        .with_local_scope(self.local_scope);
+        lexer.lex(&mut visitor);
+        let mut imports = IcssImports::default();
+        for dependency in &dependencies {
+            imports.add_dependency(source, dependency);
+        }
+        let order = imports.order(&mut warnings);
+        self.warnings
+            .extend(warnings.iter().map(|warning: &Warning| {
+                ModuleWarning::new(path, warning.range().clone(), warning.to_string())
+            }));
+
+        let mut requests = Vec::with_capacity(order.len());
+        for (request, range) in order {
+            let module = self.add_request(path, request, range);
+            requests.push((request.to_string(), module));
+        }
+
+        let handle_local_name = &mut self.handle_local_name;
+        let mut exports = ModuleExports::new(|local_name: &LocalName| {
+            handle_local_name.handle_module_local_name(path, local_name)
+        });
+        for dependency in &dependencies {
+            exports.add(dependency);
+        }
+        let mut resolved_exports = Vec::with_capacity(exports.len());
+        for export in exports.iter() {
+            let mut names: Vec<String> = Vec::new();
+            for value in &export.values {
+                match value {
+                    ExportName::Local(name) => names.push(name.clone()),
+                    ExportName::Global(name) => names.push(name.to_string()),
+                    ExportName::Import { name, from } => {
+                        let from = from.trim_matches(|c| c == '\'' || c == '"');
+                        let Some(module) = requests
+                            .iter()
+                            .find(|(request, _)| request == from)
+                            .and_then(|(_, module)| *module)
+                        else {
+                            continue;
+                        };
+                        // Circular imports are warned already
+                        if self.states[module] == ModuleState::Loading {
+                            continue;
+                        }
+                        let Some(imported) = self.modules[module].export(name) else {
+                            let start = offset_of(source, name);
+                            self.warnings.push(ModuleWarning::new(
+                                path,
+                                Range::new(start, start + name.len() as u32),
+                                format!("Can't find '{name}' in '{from}'"),
+                            ));
+                            continue;
+                        };
+                        for name in imported {
+                            if !names.contains(name) {
+                                names.push(name.clone());
+                            }
+                        }
+                    }
+                }
+            }
+            resolved_exports.push((export.name.to_string(), names));
+        }
+        let module = &mut self.modules[index];
+        module.requests = requests;
+        module.exports = resolved_exports;
+    }
+
+    fn add_request(&mut self, path: &str, request: &str, range: Range) -> Option<usize> {
+        let Some(resolved) = self.resolver.resolve_module(path, request) else {
+            self.warnings.push(ModuleWarning::new(
+                path,
+                range,
+                format!("Can't resolve '{request}'"),
+            ));
+            return None;
+        };
+        if let Some(&module) = self.indexes.get(&resolved) {
+            if self.states[module] == ModuleState::Loading {
+                self.warnings.push(ModuleWarning::new(
+                    path,
+                    range,
+                    format!("Circular import of '{resolved}'"),
+                ));
+            }
+            return Some(module);
+        }
+        let module = self.add(&resolved);
+        if module.is_none() {
+            self.warnings.push(ModuleWarning::new(
+                path,
+                range,
+                format!("Can't load '{resolved}'"),
+            ));
+        }
+        module
+    }
+}
+
+impl<R, L, N> ModuleGraph<R, L, N> {
+    pub fn get(&self, path: &str) -> Option<usize> {
+        self.indexes.get(path).copied()
+    }
+
+    pub fn module(&self, index: usize) -> &Module {
+        &self.modules[index]
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    pub fn warnings(&self) -> &[ModuleWarning] {
+        &self.warnings
+    }
+
+    /// The generated names of `name` imported from `from` by the module at
+    /// `index`, e.g. `--x` of `var(--x from "./vars.css")`, `from` may be quoted.
+    pub fn imported(&self, index: usize, from: &str, name: &str) -> Option<&[String]> {
+        let from = from.trim_matches(|c| c == '\'' || c == '"');
+        let (_, module) = self.modules[index]
+            .requests
+            .iter()
+            .find(|(request, _)| request == from)?;
+        self.modules[(*module)?].export(name)
+    }
+
+    /// The indexes of the modules in the order their CSS should be output,
+    /// every module comes after the modules it imports.
+    pub fn order(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.modules.len()];
+        let mut order = Vec::with_capacity(self.modules.len());
+        for index in 0..self.modules.len() {
+            self.visit(index, &mut visited, &mut order);
+        }
+        order
+    }
+
+    fn visit(&self, index: usize, visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[index] {
+            return;
+        }
+        visited[index] = true;
+        for dependency in self.modules[index].dependencies() {
+            self.visit(dependency, visited, order);
+        }
+        order.push(index);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::dependencies::is_reserved_keyword;
+use crate::lexer::is_ident;
+use crate::Dependency;
+use crate::LexDependencies;
+use crate::Lexer;
+use crate::LocalName;
+use crate::LocalScope;
+use crate::Mode;
+use crate::Range;
+
+const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789_-";
+
+// Names that are invalid for some kinds besides the keywords of
+// `is_reserved_keyword`, e.g. `grid-area: span`, `default` is reserved for
+// all custom identifiers
+const KEYWORDS: &[&str] = &["auto", "default", "span"];
+
+/// Assigns the shortest unused names, `a`, `b`, ..., `aa`, ..., to the local
+/// names of all modules of a project.
+///
+/// The same name of the same module always gets the same short name whatever
+/// its kind, so the exports of a module stay merged. The assignment can be
+/// persisted with `write_mapping` and restored with `from_mapping` to keep the
+/// names stable between builds.
+#[derive(Debug, Default, Clone)]
+pub struct ShortNames {
+    local_scope: LocalScope,
+    // (path, name) -> short name
+    names: BTreeMap<(String, String), String>,
+    used: HashSet<String>,
+    reserved: HashSet<String>,
+    next: usize,
+}
+
+impl ShortNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restores the assignment written by `write_mapping`, malformed lines are ignored.
+    pub fn from_mapping(mapping: &str) -> Self {
+        let mut short_names = Self::default();
+        for line in mapping.lines() {
+            let mut parts = line.splitn(3, '\t');
+            let (Some(short_name), Some(path), Some(name)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            if !short_names.used.insert(short_name.to_string()) {
+                continue;
+            }
+            short_names
+                .names
+                .insert((path.to_string(), name.to_string()), short_name.to_string());
+        }
+        short_names
+    }
+
+    pub fn with_local_scope(mut self, local_scope: LocalScope) -> Self {
+        self.local_scope = local_scope;
+        self
+    }
+
+    /// Prevents `name` from being assigned, a local name it is already
+    /// assigned to gets a new short name.
+    pub fn reserve(&mut self, name: &str) {
+        if self.used.remove(name) {
+            self.names.retain(|_, short_name| short_name != name);
+        }
+        self.reserved.insert(name.to_string());
+    }
+
+    /// Reserves every identifier of `input` that is not renamed, e.g. the
+    /// names in `:global(...)` or `composes: a from global`, so no short name
+    /// collides with a global name. Should be called for all modules before
+    /// assigning short names.
+    pub fn reserve_globals(&mut self, input: &str, mode: Mode) {
+        let mut locals: Vec<Range> = Vec::new();
+        let mut lexer = Lexer::new(input);
+        let mut visitor = LexDependencies::new(
+            |dependency: Dependency| match &dependency {
+                Dependency::Composes {
+                    from: Some("global"),
+                    ..
+                } => {}
+                Dependency::Composes { range, .. } => locals.push(range.clone()),
+                dependency => {
+                    if let Some(local_name) = dependency.local_name() {
+                        locals.push(local_name.range);
+                    }
+                }
+            },
+            |_| {},
+            mode,
+        )
+        .with_local_scope(self.local_scope);
+        lexer.lex(&mut visitor);
+
+        let mut start = None;
+        for (i, c) in input.char_indices().chain([(input.len(), ' ')]) {
+            match start {
+                None if is_ident(c) => start = Some(i),
+                Some(word_start) if !is_ident(c) => {
+                    start = None;
+                    let (word_start, word_end) = (word_start as u32, i as u32);
+                    if locals
+                        .iter()
+                        .any(|local| local.start < word_end && word_start < local.end)
+                    {
+                        continue;
+                    }
+                    // `--a` collides with the short name `a` of a custom property
+                    let word = input[word_start as usize..i].trim_start_matches('-');
+                    if !word.is_empty() {
+                        self.reserve(word);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The short name of `local_name` of the module at `path`, assigned on first use.
+    pub fn name(&mut self, path: &str, local_name: &LocalName) -> String {
+        let key = (path.to_string(), local_name.name.to_string());
+        if let Some(short_name) = self.names.get(&key) {
+            return short_name.clone();
+        }
+        let short_name = loop {
+            let short_name = short_name(self.next);
+            self.next += 1;
+            if !self.used.contains(&short_name)
+                && !self.reserved.contains(&short_name)
+                && !KEYWORDS.contains(&short_name.as_str())
+                && !is_reserved_keyword(&short_name)
+            {
+                break short_name;
+            }
+        };
+        self.used.insert(short_name.clone());
+        self.names.insert(key, short_name.clone());
+        short_name
+    }
+
+    /// Writes the assignment as `short name\tpath\tname` lines, sorted by path and name.
+    pub fn write_mapping(&self, output: &mut impl fmt::Write) -> fmt::Result {
+        for ((path, name), short_name) in &self.names {
+            writeln!(output, "{short_name}\t{path}\t{name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The `index`th name of `a`, ..., `z`, `aa`, `ba`, ..., every name is a valid identifier.
+fn short_name(mut index: usize) -> String {
+    let mut name = String::new();
+    name.push(FIRST[index % FIRST.len()] as char);
+    index /= FIRST.len();
+    while index > 0 {
+        index -= 1;
+        name.push(REST[index % REST.len()] as char);
+        index /= REST.len();
+    }
+    name
+}
@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+
+use crate::Pos;
+use crate::Range;
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Source map revision 3, `Display` writes it as JSON.
+#[derive(Debug, Default, Clone, Hash, PartialEq, Eq)]
+pub struct SourceMap {
+    pub file: Option<String>,
+    pub sources: Vec<String>,
+    pub sources_content: Vec<Option<String>>,
+    pub names: Vec<String>,
+    pub mappings: String,
+}
+
+impl SourceMap {
+    /// Parses a source map revision 3, index maps with `sections` are not supported.
+    pub fn from_json(json: &str) -> Option<Self> {
+        let mut parser = JsonParser {
+            chars: json.chars().peekable(),
+        };
+        let JsonValue::Object(fields) = parser.parse_value()? else {
+            return None;
+        };
+        let mut source_map = SourceMap::default();
+        let mut source_root = None;
+        for (key, value) in fields {
+            match (key.as_str(), value) {
+                ("version", JsonValue::Number(version)) if version != 3.0 => return None,
+                ("file", JsonValue::String(file)) => source_map.file = Some(file),
+                ("sourceRoot", JsonValue::String(root)) if !root.is_empty() => {
+                    source_root = Some(root)
+                }
+                ("sources", JsonValue::Array(sources)) => {
+                    source_map.sources = sources
+                        .into_iter()
+                        .map(|source| match source {
+                            JsonValue::String(source) => source,
+                            _ => String::new(),
+                        })
+                        .collect()
+                }
+                ("sourcesContent", JsonValue::Array(contents)) => {
+                    source_map.sources_content = contents
+                        .into_iter()
+                        .map(|content| match content {
+                            JsonValue::String(content) => Some(content),
+                            _ => None,
+                        })
+                        .collect()
+                }
+                ("names", JsonValue::Array(names)) => {
+                    source_map.names = names
+                        .into_iter()
+                        .filter_map(|name| match name {
+                            JsonValue::String(name) => Some(name),
+                            _ => None,
+                        })
+                        .collect()
+                }
+                ("mappings", JsonValue::String(mappings)) => source_map.mappings = mappings,
+                ("sections", _) => return None,
+                _ => {}
+            }
+        }
+        if let Some(root) = source_root {
+            let root = root.trim_end_matches('/');
+            for source in &mut source_map.sources {
+                *source = format!("{root}/{source}");
+            }
+        }
+        Some(source_map)
+    }
+}
+
+impl Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{\"version\":3,")?;
+        if let Some(file) = &self.file {
+            write!(f, "\"file\":")?;
+            write_json_string(f, file)?;
+            write!(f, ",")?;
+        }
+        write!(f, "\"sources\":[")?;
+        for (i, source) in self.sources.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write_json_string(f, source)?;
+        }
+        write!(f, "],\"sourcesContent\":[")?;
+        for (i, content) in self.sources_content.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            match content {
+                Some(content) => write_json_string(f, content)?,
+                None => write!(f, "null")?,
+            }
+        }
+        write!(f, "],\"names\":[")?;
+        for (i, name) in self.names.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write_json_string(f, name)?;
+        }
+        write!(f, "],\"mappings\":")?;
+        write_json_string(f, &self.mappings)?;
+        write!(f, "}}")
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+struct LineCol {
+    line: u32,
+    // In UTF-16 code units, the same as JavaScript strings
+    col: u32,
+}
+
+impl LineCol {
+    fn advance(&mut self, c: char) {
+        if c == '\n' {
+            self.line += 1;
+            self.col = 0;
+        } else {
+            self.col += c.len_utf16() as u32;
+        }
+    }
+}
+
+/// Builds the mappings of a single source while its transformed output is written.
+#[derive(Debug, Default)]
+pub(crate) struct SourceMapBuilder<'s> {
+    names: Vec<&'s str>,
+    name_indices: HashMap<&'s str, u32>,
+    mappings: String,
+    generated: LineCol,
+    original: LineCol,
+    prev_generated: LineCol,
+    prev_original: LineCol,
+    prev_name: u32,
+    has_segment: bool,
+}
+
+impl<'s> SourceMapBuilder<'s> {
+    /// Unchanged text, every line of it maps to the same line of the source.
+    pub fn copy(&mut self, text: &str) {
+        let mut chars = text.chars().peekable();
+        if chars.peek().is_some() {
+            self.add_mapping(None);
+        }
+        while let Some(c) = chars.next() {
+            self.generated.advance(c);
+            self.original.advance(c);
+            if c == '\n' && chars.peek().is_some() {
+                self.add_mapping(None);
+            }
+        }
+    }
+
+    /// `original` is replaced by `content`, `name` is the original name of a renamed local.
+    pub fn replace(&mut self, original: &str, content: &str, name: Option<&'s str>) {
+        if !content.is_empty() {
+            self.add_mapping(name);
+        }
+        content.chars().for_each(|c| self.generated.advance(c));
+        original.chars().for_each(|c| self.original.advance(c));
+    }
+
+    pub fn build(self, source: &str, source_content: &str) -> SourceMap {
+        SourceMap {
+            file: None,
+            sources: vec![source.to_string()],
+            sources_content: vec![Some(source_content.to_string())],
+            names: self.names.iter().map(|name| name.to_string()).collect(),
+            mappings: self.mappings,
+        }
+    }
+
+    fn add_mapping(&mut self, name: Option<&'s str>) {
+        if self.generated.line != self.prev_generated.line {
+            for _ in self.prev_generated.line..self.generated.line {
+                self.mappings.push(';');
+            }
+            self.prev_generated = LineCol {
+                line: self.generated.line,
+                col: 0,
+            };
+        } else if self.has_segment {
+            self.mappings.push(',');
+        }
+        self.has_segment = true;
+        encode_vlq(
+            &mut self.mappings,
+            self.generated.col as i64 - self.prev_generated.col as i64,
+        );
+        // Always the first and only source
+        encode_vlq(&mut self.mappings, 0);
+        encode_vlq(
+            &mut self.mappings,
+            self.original.line as i64 - self.prev_original.line as i64,
+        );
+        encode_vlq(
+            &mut self.mappings,
+            self.original.col as i64 - self.prev_original.col as i64,
+        );
+        if let Some(name) = name {
+            let next_index = self.names.len() as u32;
+            let index = *self.name_indices.entry(name).or_insert(next_index);
+            if index == next_index {
+                self.names.push(name);
+            }
+            encode_vlq(&mut self.mappings, index as i64 - self.prev_name as i64);
+            self.prev_name = index;
+        }
+        self.prev_generated.col = self.generated.col;
+        self.prev_original = self.original;
+    }
+}
+
+fn encode_vlq(output: &mut String, value: i64) {
+    let mut vlq = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = vlq & 0b11111;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 0b100000;
+        }
+        output.push(BASE64_CHARS[digit as usize] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+#[derive(Debug)]
+enum JsonValue {
+    Null,
+    Bool,
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn skip_white_space(&mut self) {
+        while matches!(self.chars.peek(), Some(' ' | '\n' | '\r' | '\t')) {
+            self.chars.next();
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_white_space();
+        if self.chars.peek() == Some(&c) {
+            self.chars.next();
+            return true;
+        }
+        false
+    }
+
+    fn parse_value(&mut self) -> Option<JsonValue> {
+        self.skip_white_space();
+        match self.chars.peek()? {
+            '{' => {
+                self.chars.next();
+                let mut fields = Vec::new();
+                if self.eat('}') {
+                    return Some(JsonValue::Object(fields));
+                }
+                loop {
+                    self.skip_white_space();
+                    let key = self.parse_string()?;
+                    if !self.eat(':') {
+                        return None;
+                    }
+                    fields.push((key, self.parse_value()?));
+                    if self.eat('}') {
+                        return Some(JsonValue::Object(fields));
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            '[' => {
+                self.chars.next();
+                let mut items = Vec::new();
+                if self.eat(']') {
+                    return Some(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.parse_value()?);
+                    if self.eat(']') {
+                        return Some(JsonValue::Array(items));
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            '"' => self.parse_string().map(JsonValue::String),
+            _ => {
+                let mut literal = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                        break;
+                    }
+                    literal.push(c);
+                    self.chars.next();
+                }
+                match literal.as_str() {
+                    "null" => Some(JsonValue::Null),
+                    "true" | "false" => Some(JsonValue::Bool),
+                    _ => literal.parse().ok().map(JsonValue::Number),
+                }
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Option<String> {
+        if self.chars.next()? != '"' {
+            return None;
+        }
+        let mut s = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(s),
+                '\\' => match self.chars.next()? {
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'u' => {
+                        let high = self.parse_hex4()?;
+                        let code = if (0xd800..0xdc00).contains(&high) {
+                            if self.chars.next()? != '\\' || self.chars.next()? != 'u' {
+                                return None;
+                            }
+                            let low = self.parse_hex4()?;
+                            0x10000 + ((high - 0xd800) << 10) + (low.checked_sub(0xdc00)?)
+                        } else {
+                            high
+                        };
+                        s.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    c => s.push(c),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Option<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            code = code * 16 + self.chars.next()?.to_digit(16)?;
+        }
+        Some(code)
+    }
+}
+
+/// A zero-based position in an original source.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct OriginalPosition<'m> {
+    pub source: &'m str,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Display for OriginalPosition<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.source, self.line + 1, self.column + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct OriginalRange<'m> {
+    pub start: OriginalPosition<'m>,
+    pub end: OriginalPosition<'m>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    generated_column: u32,
+    source: u32,
+    line: u32,
+    column: u32,
+}
+
+/// Translates ranges of the generated input back to the sources of its source map.
+#[derive(Debug)]
+pub struct InputSourceMap<'s, 'm> {
+    source_map: &'m SourceMap,
+    input: &'s str,
+    line_starts: Vec<usize>,
+    lines: Vec<Vec<Segment>>,
+}
+
+impl<'s, 'm> InputSourceMap<'s, 'm> {
+    pub fn new(source_map: &'m SourceMap, input: &'s str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source_map,
+            input,
+            line_starts,
+            lines: decode_mappings(&source_map.mappings),
+        }
+    }
+
+    pub fn original_position(&self, pos: Pos) -> Option<OriginalPosition<'m>> {
+        let pos = pos as usize;
+        let line = self.line_starts.partition_point(|&start| start <= pos) - 1;
+        let column = self
+            .input
+            .get(self.line_starts[line]..pos)?
+            .encode_utf16()
+            .count() as u32;
+        let segments = self.lines.get(line)?;
+        let index = segments.partition_point(|segment| segment.generated_column <= column);
+        let segment = segments.get(index.checked_sub(1)?)?;
+        // The segment maps its start, the rest of it is assumed unchanged
+        Some(OriginalPosition {
+            source: self.source_map.sources.get(segment.source as usize)?,
+            line: segment.line,
+            column: segment.column + (column - segment.generated_column),
+        })
+    }
+
+    pub fn original_range(&self, range: &Range) -> Option<OriginalRange<'m>> {
+        Some(OriginalRange {
+            start: self.original_position(range.start)?,
+            end: self.original_position(range.end)?,
+        })
+    }
+}
+
+fn decode_mappings(mappings: &str) -> Vec<Vec<Segment>> {
+    let mut lines = Vec::new();
+    let mut source = 0;
+    let mut line = 0;
+    let mut column = 0;
+    for mappings in mappings.split(';') {
+        let mut segments = Vec::new();
+        let mut generated_column = 0;
+        for segment in mappings.split(',') {
+            let mut fields = [0i64; 5];
+            let Some(len) = decode_vlq(segment, &mut fields) else {
+                continue;
+            };
+            generated_column += fields[0];
+            if len < 4 {
+                continue;
+            }
+            source += fields[1];
+            line += fields[2];
+            column += fields[3];
+            if generated_column < 0 || source < 0 || line < 0 || column < 0 {
+                continue;
+            }
+            segments.push(Segment {
+                generated_column: generated_column as u32,
+                source: source as u32,
+                line: line as u32,
+                column: column as u32,
+            });
+        }
+        segments.sort_by_key(|segment| segment.generated_column);
+        lines.push(segments);
+    }
+    lines
+}
+
+fn decode_vlq(segment: &str, fields: &mut [i64; 5]) -> Option<usize> {
+    let mut len = 0;
+    let mut value = 0;
+    let mut shift = 0;
+    for c in segment.bytes() {
+        let digit = BASE64_CHARS.iter().position(|&b| b == c)? as i64;
+        // The values are 32-bit, more continuation digits are malformed
+        if shift > 30 {
+            return None;
+        }
+        value += (digit & 0b11111) << shift;
+        if digit & 0b100000 != 0 {
+            shift += 5;
+            continue;
+        }
+        *fields.get_mut(len)? = if value & 1 == 1 {
+            -(value >> 1)
+        } else {
+            value >> 1
+        };
+        len += 1;
+        value = 0;
+        shift = 0;
+    }
+    (len > 0).then_some(len)
+}
@@ -0,0 +1,323 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::icss::IcssImports;
+use crate::source_map::SourceMapBuilder;
+use crate::tree_shaking::TreeShaking;
+use crate::Dependency;
+use crate::ExportName;
+use crate::HandleLocalName;
+use crate::LexDependencies;
+use crate::Lexer;
+use crate::LocalName;
+use crate::LocalScope;
+use crate::Mode;
+use crate::ModuleExports;
+use crate::Pos;
+use crate::Range;
+use crate::SourceMap;
+use crate::Warning;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct Replacement<'s> {
+    range: Range,
+    content: Cow<'s, str>,
+    // The original name of a renamed local
+    name: Option<&'s str>,
+}
+
+#[derive(Debug, Default)]
+struct Icss<'s> {
+    imports: IcssImports<'s>,
+    // Dependencies of the `:export` block
+    dependencies: Vec<Dependency<'s>>,
+}
+
+/// Applies the dependencies of a css module to its input, renaming every
+/// local name through `handle_local_name`.
+#[derive(Debug)]
+pub struct Transformer<'s, N> {
+    input: &'s str,
+    mode: Mode,
+    local_scope: LocalScope,
+    used_exports: Option<HashSet<String>>,
+    scope_attribute: Option<String>,
+    handle_local_name: N,
+}
+
+impl<'s, N: HandleLocalName<'s>> Transformer<'s, N> {
+    pub fn new(input: &'s str, mode: Mode, handle_local_name: N) -> Self {
+        Self {
+            input,
+            mode,
+            local_scope: LocalScope::default(),
+            used_exports: None,
+            scope_attribute: None,
+            handle_local_name,
+        }
+    }
+
+    pub fn with_local_scope(mut self, local_scope: LocalScope) -> Self {
+        self.local_scope = local_scope;
+        self
+    }
+
+    /// Removes the top-level rules whose selectors only match unused local
+    /// classes and ids, and the unused `@keyframes` and `@counter-style`.
+    /// `used_exports` are the names of the exports in use, before any
+    /// `exportLocalsConvention` is applied.
+    pub fn with_used_exports(
+        mut self,
+        used_exports: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.used_exports = Some(used_exports.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Scopes the selectors of `Mode::Scoped` by `scope_attribute`, e.g.
+    /// `data-v-7ba5bd90` is inserted as `[data-v-7ba5bd90]`, and as
+    /// `[data-v-7ba5bd90-s]` into the selectors of `:slotted()`.
+    pub fn with_scope_attribute(mut self, scope_attribute: impl Into<String>) -> Self {
+        self.scope_attribute = Some(scope_attribute.into());
+        self
+    }
+
+    pub fn transform(
+        &mut self,
+        output: &mut impl fmt::Write,
+    ) -> Result<Vec<Warning<'s>>, fmt::Error> {
+        let (replacements, warnings) = self.replacements(None);
+        self.write(replacements, output, None)?;
+        Ok(warnings)
+    }
+
+    /// Same as `transform`, but writes Interoperable CSS: names imported by
+    /// `composes`, `var()` and `@value` are hoisted into `:import` blocks, and
+    /// the exports are written into an `:export` block.
+    pub fn transform_icss(
+        &mut self,
+        output: &mut impl fmt::Write,
+    ) -> Result<Vec<Warning<'s>>, fmt::Error> {
+        let mut icss = Icss::default();
+        let (replacements, mut warnings) = self.replacements(Some(&mut icss));
+        icss.imports.write(output, &mut warnings)?;
+
+        let mut body = String::new();
+        self.write(replacements, &mut body, None)?;
+        output.write_str(&body)?;
+
+        let handle_local_name = &mut self.handle_local_name;
+        let mut exports = ModuleExports::new(|local_name: &LocalName<'s>| {
+            handle_local_name.handle_local_name(local_name)
+        });
+        for dependency in &icss.dependencies {
+            exports.add(dependency);
+        }
+        if exports.is_empty() {
+            return Ok(warnings);
+        }
+        if !body.is_empty() && !body.ends_with('\n') {
+            output.write_char('\n')?;
+        }
+        output.write_str(":export {\n")?;
+        for export in exports.iter() {
+            write!(output, "    {}:", export.name)?;
+            for value in &export.values {
+                match value {
+                    ExportName::Local(name) => write!(output, " {name}")?,
+                    ExportName::Global(name) => write!(output, " {name}")?,
+                    ExportName::Import { name, from } => {
+                        write!(output, " {}", icss.imports.alias(from, name))?
+                    }
+                }
+            }
+            output.write_str(";\n")?;
+        }
+        output.write_str("}\n")?;
+        Ok(warnings)
+    }
+
+    /// Same as `transform`, also returns the source map of the output,
+    /// `source` is the name of the input in the source map.
+    pub fn transform_with_source_map(
+        &mut self,
+        output: &mut impl fmt::Write,
+        source: &str,
+    ) -> Result<(Vec<Warning<'s>>, SourceMap), fmt::Error> {
+        let mut builder = SourceMapBuilder::default();
+        let (replacements, warnings) = self.replacements(None);
+        self.write(replacements, output, Some(&mut builder))?;
+        Ok((warnings, builder.build(source, self.input)))
+    }
+
+    fn write(
+        &self,
+        replacements: Vec<Replacement<'s>>,
+        output: &mut impl fmt::Write,
+        mut source_map: Option<&mut SourceMapBuilder<'s>>,
+    ) -> fmt::Result {
+        let input = self.input;
+        let mut index = 0;
+        for Replacement {
+            range,
+            content,
+            name,
+        } in replacements
+        {
+            let unchanged = slice(input, index, range.start);
+            output.write_str(unchanged)?;
+            output.write_str(&content)?;
+            if let Some(source_map) = &mut source_map {
+                source_map.copy(unchanged);
+                source_map.replace(slice(input, range.start, range.end), &content, name);
+            }
+            index = range.end;
+        }
+        let unchanged = slice(input, index, input.len() as Pos);
+        output.write_str(unchanged)?;
+        if let Some(source_map) = source_map {
+            source_map.copy(unchanged);
+        }
+        Ok(())
+    }
+
+    fn replacements(
+        &mut self,
+        mut icss: Option<&mut Icss<'s>>,
+    ) -> (Vec<Replacement<'s>>, Vec<Warning<'s>>) {
+        let input = self.input;
+        let handle_local_name = &mut self.handle_local_name;
+        let mut values = HashMap::new();
+        let mut replacements = Vec::new();
+        let mut warnings = Vec::new();
+        let mut tree_shaking = self.used_exports.as_ref().map(|_| TreeShaking::default());
+        let rule_boundaries = tree_shaking.is_some();
+        let scope_attributes = self
+            .scope_attribute
+            .as_ref()
+            .map(|attribute| (format!("[{attribute}]"), format!("[{attribute}-s]")));
+        let mut lexer = Lexer::new(input);
+        let mut visitor = LexDependencies::new(
+            |dependency: Dependency<'s>| {
+                if let Some(icss) = &mut icss {
+                    icss.add(input, &dependency);
+                }
+                if let Some(tree_shaking) = &mut tree_shaking {
+                    tree_shaking.add_dependency(input, &dependency);
+                }
+                let replacement = match dependency {
+                    Dependency::Replace { content, range } => Replacement {
+                        range,
+                        content: Cow::Borrowed(content),
+                        name: None,
+                    },
+                    Dependency::ScopeInsertion { range, slotted } => {
+                        let Some((attribute, slotted_attribute)) = &scope_attributes else {
+                            return;
+                        };
+                        Replacement {
+                            range,
+                            content: Cow::Owned(if slotted {
+                                slotted_attribute.clone()
+                            } else {
+                                attribute.clone()
+                            }),
+                            name: None,
+                        }
+                    }
+                    Dependency::ValueDecl { name, value } => {
+                        let value = values.get(value).copied().unwrap_or(value);
+                        values.insert(name, value);
+                        return;
+                    }
+                    Dependency::ValueUsage { name, range } => {
+                        let Some(value) = values.get(name) else {
+                            return;
+                        };
+                        Replacement {
+                            range,
+                            content: Cow::Borrowed(*value),
+                            name: Some(name),
+                        }
+                    }
+                    dependency => {
+                        let Some(local_name) = dependency.local_name() else {
+                            return;
+                        };
+                        let Range { start, end } = local_name.range;
+                        let prefix = slice(input, start, end - local_name.name.len() as Pos);
+                        let content = match (&mut icss, local_name.from) {
+                            // `--x from "./file.css"` is imported as `--i__imported_x_0`
+                            (Some(icss), Some(from)) => icss
+                                .imports
+                                .alias(from, slice(input, start, end))
+                                .to_string(),
+                            _ => {
+                                let new_name = handle_local_name.handle_local_name(&local_name);
+                                format!("{prefix}{new_name}")
+                            }
+                        };
+                        // Drop the `from "./file.css"` part of `var(--x from "./file.css")`
+                        let end = local_name
+                            .from
+                            .map_or(end, |from| offset_of(input, from) + from.len() as Pos);
+                        Replacement {
+                            range: Range::new(start, end),
+                            content: Cow::Owned(content),
+                            name: Some(local_name.name),
+                        }
+                    }
+                };
+                replacements.push(replacement);
+            },
+            |warning| warnings.push(warning),
+            self.mode,
+        )
+        .with_local_scope(self.local_scope)
+        .with_rule_boundaries(rule_boundaries);
+        lexer.lex(&mut visitor);
+        if let Some((tree_shaking, used_exports)) = tree_shaking.zip(self.used_exports.as_ref()) {
+            let unused = tree_shaking.unused(used_exports);
+            replacements.retain(|replacement: &Replacement| {
+                !unused.iter().any(|range| {
+                    range.start <= replacement.range.start && replacement.range.end <= range.end
+                })
+            });
+            replacements.extend(unused.into_iter().map(|range| Replacement {
+                range,
+                content: Cow::Borrowed(""),
+                name: None,
+            }));
+        }
+        // Dependencies are not strictly in source order, e.g. `animation` names
+        // are only known at the end of the declaration
+        replacements.sort_by_key(|replacement| replacement.range.start);
+        let mut index = 0;
+        replacements.retain(|replacement| {
+            if replacement.range.start < index {
+                return false;
+            }
+            index = replacement.range.end;
+            true
+        });
+        (replacements, warnings)
+    }
+}
+
+impl<'s> Icss<'s> {
+    fn add(&mut self, input: &'s str, dependency: &Dependency<'s>) {
+        self.imports.add_dependency(input, dependency);
+        self.dependencies.push(dependency.clone());
+    }
+}
+
+fn slice(input: &str, start: Pos, end: Pos) -> &str {
+    &input[start as usize..end as usize]
+}
+
+/// The offset of `sub`, a slice of `input`.
+pub(crate) fn offset_of(input: &str, sub: &str) -> Pos {
+    (sub.as_ptr() as usize - input.as_ptr() as usize) as Pos
+}
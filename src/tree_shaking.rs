@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use crate::Dependency;
+use crate::Range;
+
+#[derive(Debug)]
+struct Rule<'s> {
+    range: Range,
+    // The local classes and ids of the prelude
+    locals: Vec<Local<'s>>,
+    // The number of comma separated selectors of the prelude
+    selectors: usize,
+    keyframes: Option<&'s str>,
+    counter_style: Option<&'s str>,
+    // The keyframes and counter styles the rule refers to
+    references: Vec<&'s str>,
+}
+
+impl Default for Rule<'_> {
+    fn default() -> Self {
+        Self {
+            range: Range::new(0, 0),
+            locals: Vec::new(),
+            selectors: 1,
+            keyframes: None,
+            counter_style: None,
+            references: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Local<'s> {
+    name: &'s str,
+    range: Range,
+    // The index of the selector it is in
+    selector: usize,
+    // Inside a pseudo function other than `:local()` and `:global()`, e.g. `:not(.a)`
+    nested: bool,
+}
+
+/// The top-level rules of a css module, used to remove the rules of local
+/// names that are not used.
+#[derive(Debug, Default)]
+pub(crate) struct TreeShaking<'s> {
+    rules: Vec<Rule<'s>>,
+    // The dependencies of the current rule, `Dependency::Rule` comes after them
+    current: Rule<'s>,
+    // (local classes, composed names) of `composes`
+    composes: Vec<(Vec<&'s str>, Vec<&'s str>)>,
+}
+
+impl<'s> TreeShaking<'s> {
+    pub fn add_dependency(&mut self, input: &'s str, dependency: &Dependency<'s>) {
+        match dependency {
+            Dependency::Rule { range } => {
+                let mut rule = std::mem::take(&mut self.current);
+                rule.range = range.clone();
+                let prelude = prelude(input, range);
+                let prelude_end = range.start + prelude.len() as u32;
+                rule.locals.retain(|local| local.range.end <= prelude_end);
+                for local in &mut rule.locals {
+                    (local.selector, local.nested) =
+                        selector_at(prelude, (local.range.start - range.start) as usize);
+                }
+                rule.selectors = selector_at(prelude, prelude.len()).0 + 1;
+                self.rules.push(rule);
+            }
+            Dependency::LocalClass { name, range, .. }
+            | Dependency::LocalId { name, range, .. } => self.current.locals.push(Local {
+                name: &name[1..],
+                range: range.clone(),
+                selector: 0,
+                nested: false,
+            }),
+            Dependency::LocalKeyframesDecl { name, .. } => self.current.keyframes = Some(name),
+            Dependency::LocalCounterStyleDecl { name, .. } => {
+                self.current.counter_style = Some(name)
+            }
+            Dependency::LocalKeyframes { name, .. }
+            | Dependency::LocalCounterStyle { name, .. } => self.current.references.push(name),
+            Dependency::Composes {
+                local_classes,
+                names,
+                from: None,
+                ..
+            } => self.composes.push((local_classes.to_vec(), names.to_vec())),
+            _ => {}
+        }
+    }
+
+    /// The ranges of the rules to remove, `used` are the names of the exports in use.
+    pub fn unused(&self, used: &HashSet<String>) -> Vec<Range> {
+        let mut used: HashSet<&str> = used.iter().map(String::as_str).collect();
+        // Composed classes are used by the classes composing them
+        loop {
+            let len = used.len();
+            for (local_classes, names) in &self.composes {
+                if local_classes.iter().any(|class| used.contains(class)) {
+                    used.extend(names.iter());
+                }
+            }
+            if used.len() == len {
+                break;
+            }
+        }
+
+        let mut removed = vec![false; self.rules.len()];
+        for (rule, removed) in self.rules.iter().zip(&mut removed) {
+            *removed = rule.is_unused_style_rule(&used);
+        }
+        // Keyframes and counter styles are used by the rules that are kept,
+        // counter styles can also refer to each other, e.g. `system: extends a`
+        loop {
+            let len = used.len();
+            for (rule, removed) in self.rules.iter().zip(&removed) {
+                let is_used = match (rule.keyframes, rule.counter_style) {
+                    (Some(name), _) | (_, Some(name)) => used.contains(name),
+                    _ => !removed,
+                };
+                if is_used {
+                    used.extend(rule.references.iter());
+                }
+            }
+            if used.len() == len {
+                break;
+            }
+        }
+
+        self.rules
+            .iter()
+            .zip(removed)
+            .filter(
+                |(rule, removed)| match (rule.keyframes, rule.counter_style) {
+                    (Some(name), _) | (_, Some(name)) => !used.contains(name),
+                    _ => *removed,
+                },
+            )
+            .map(|(rule, _)| rule.range.clone())
+            .collect()
+    }
+}
+
+impl Rule<'_> {
+    /// A style rule is unused when every selector of it has an unused local
+    /// class or id outside of pseudo functions, e.g. `.unused .used` never matches.
+    fn is_unused_style_rule(&self, used: &HashSet<&str>) -> bool {
+        if self.locals.is_empty() || self.keyframes.is_some() || self.counter_style.is_some() {
+            return false;
+        }
+        (0..self.selectors).all(|selector| {
+            self.locals.iter().any(|local| {
+                local.selector == selector && !local.nested && !used.contains(local.name)
+            })
+        })
+    }
+}
+
+/// The prelude of a rule, up to its '{'.
+fn prelude<'s>(input: &'s str, range: &Range) -> &'s str {
+    let rule = &input[range.start as usize..range.end as usize];
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in rule.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (_, '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => return &rule[..i],
+            _ => {}
+        }
+    }
+    rule
+}
+
+/// The index of the selector at `offset` of the prelude, and whether it is
+/// inside a pseudo function other than `:local()` and `:global()`.
+fn selector_at(prelude: &str, offset: usize) -> (usize, bool) {
+    let mut selector = 0;
+    // Whether each open parenthesis is of `:local(` or `:global(`
+    let mut parentheses = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in prelude[..offset].char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (_, '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => {
+                let before = prelude[..i].to_ascii_lowercase();
+                parentheses.push(before.ends_with(":local") || before.ends_with(":global"));
+            }
+            (None, ')') => {
+                parentheses.pop();
+            }
+            (None, ',') if parentheses.is_empty() => selector += 1,
+            _ => {}
+        }
+    }
+    (selector, parentheses.contains(&false))
+}
@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::lexer::is_white_space;
+use crate::Dependency;
+use crate::LexDependencies;
+use crate::Lexer;
+use crate::Mode;
+use crate::Pos;
+use crate::Range;
+use crate::RewriteUrl;
+use crate::Warning;
+
+/// Rewrites the requests of `url()`, `image-set()` and `@import` through
+/// `rewrite_url`, the rewritten URLs are escaped for the form they are written in.
+#[derive(Debug)]
+pub struct UrlRewriter<'s, U> {
+    input: &'s str,
+    rewrite_url: U,
+}
+
+impl<'s, U: RewriteUrl> UrlRewriter<'s, U> {
+    pub fn new(input: &'s str, rewrite_url: U) -> Self {
+        Self { input, rewrite_url }
+    }
+
+    /// Writes the input with the URLs that `rewrite_url` returns `Some` for
+    /// replaced, `rewrite_url` receives the requests with CSS escapes resolved.
+    pub fn rewrite(
+        &mut self,
+        output: &mut impl fmt::Write,
+    ) -> Result<Vec<Warning<'s>>, fmt::Error> {
+        let input = self.input;
+        let mut urls = Vec::new();
+        let mut warnings = Vec::new();
+        let mut lexer = Lexer::new(input);
+        let mut visitor = LexDependencies::new(
+            |dependency| match dependency {
+                Dependency::Url { request, range, .. } => urls.push((request, range)),
+                Dependency::Import { request, range, .. } => {
+                    let statement = Lexer::slice_range(input, &range).unwrap_or_default();
+                    if let Some(url) = import_url(statement) {
+                        let url = Range::new(range.start + url.start, range.start + url.end);
+                        urls.push((request, url));
+                    }
+                }
+                _ => {}
+            },
+            |warning| warnings.push(warning),
+            Mode::Css,
+        );
+        lexer.lex(&mut visitor);
+        urls.sort_by_key(|(_, range)| range.start);
+
+        let mut index = 0;
+        for (request, range) in urls {
+            let Some(url) = self.rewrite_url.rewrite_url(&unescape(request)) else {
+                continue;
+            };
+            output.write_str(&input[index..range.start as usize])?;
+            let original = &input[range.start as usize..range.end as usize];
+            match original.chars().next() {
+                // `"a.png"` in `url("a.png")`, `image-set("a.png" 1x)` and `@import "a.css"`
+                Some(quote @ ('"' | '\'')) => write_string(output, &url, quote)?,
+                _ if url.chars().all(is_unquoted_url) => write!(output, "url({url})")?,
+                _ => {
+                    output.write_str("url(")?;
+                    write_string(output, &url, '"')?;
+                    output.write_char(')')?;
+                }
+            }
+            index = range.end as usize;
+        }
+        output.write_str(&input[index..])?;
+        Ok(warnings)
+    }
+}
+
+/// The range of the URL of an `@import` statement, relative to the statement:
+/// the string of `@import "a.css"` and `@import url("a.css")`, or the whole `url(a.css)`.
+pub(crate) fn import_url(statement: &str) -> Option<Range> {
+    let mut lexer = Lexer::new(statement);
+    // Skip `@import`
+    lexer.consume();
+    lexer.consume();
+    lexer.consume_ident_sequence()?;
+    lexer.consume_white_space_and_comments()?;
+    let start = lexer.cur_pos()?;
+    let c = lexer.cur()?;
+    if c == '"' || c == '\'' {
+        return consume_string(&mut lexer, c).map(|end| Range::new(start, end));
+    }
+    lexer.consume_ident_sequence()?;
+    if !lexer
+        .slice(start, lexer.cur_pos()?)?
+        .eq_ignore_ascii_case("url")
+        || lexer.cur()? != '('
+    {
+        return None;
+    }
+    lexer.consume();
+    while is_white_space(lexer.cur()?) {
+        lexer.consume();
+    }
+    let c = lexer.cur()?;
+    if c == '"' || c == '\'' {
+        let string_start = lexer.cur_pos()?;
+        return consume_string(&mut lexer, c).map(|end| Range::new(string_start, end));
+    }
+    loop {
+        match lexer.cur()? {
+            '\\' => lexer.consume(),
+            ')' => {
+                lexer.consume();
+                return Some(Range::new(start, lexer.cur_pos()?));
+            }
+            _ => {}
+        }
+        lexer.consume();
+    }
+}
+
+/// Consumes a string starting with `quote`, returns the end of the string.
+fn consume_string(lexer: &mut Lexer, quote: char) -> Option<Pos> {
+    lexer.consume();
+    loop {
+        let c = lexer.cur()?;
+        lexer.consume();
+        if c == quote {
+            return lexer.cur_pos();
+        }
+        if c == '\\' {
+            lexer.consume();
+        }
+    }
+}
+
+/// Resolves the escapes of a request, e.g. `a\ b.png` and `\61 .png`.
+fn unescape(request: &str) -> Cow<str> {
+    if !request.contains('\\') {
+        return Cow::Borrowed(request);
+    }
+    let mut unescaped = String::with_capacity(request.len());
+    let mut chars = request.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        let mut hex = String::new();
+        while hex.len() < 6 {
+            match chars.peek() {
+                Some(&c) if c.is_ascii_hexdigit() => {
+                    hex.push(c);
+                    chars.next();
+                }
+                _ => break,
+            }
+        }
+        if hex.is_empty() {
+            match chars.next() {
+                // An escaped newline in a string is a line continuation
+                Some('\n' | '\r' | '\x0c') | None => {}
+                Some(c) => unescaped.push(c),
+            }
+            continue;
+        }
+        if chars.peek().is_some_and(|&c| is_white_space(c)) {
+            chars.next();
+        }
+        let code_point = u32::from_str_radix(&hex, 16).unwrap_or_default();
+        unescaped.push(
+            char::from_u32(code_point)
+                .filter(|&c| c != '\0')
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        );
+    }
+    Cow::Owned(unescaped)
+}
+
+fn is_unquoted_url(c: char) -> bool {
+    !matches!(c, '"' | '\'' | '(' | ')' | '\\') && !is_white_space(c) && !c.is_control()
+}
+
+fn write_string(output: &mut impl fmt::Write, value: &str, quote: char) -> fmt::Result {
+    output.write_char(quote)?;
+    for c in value.chars() {
+        match c {
+            '\\' => output.write_str("\\\\")?,
+            c if c == quote => write!(output, "\\{c}")?,
+            // Newlines can't be in strings, e.g. `\a ` for `\n`
+            c if c.is_control() => write!(output, "\\{:x} ", c as u32)?,
+            c => output.write_char(c)?,
+        }
+    }
+    output.write_char(quote)
+}
@@ -1,5 +1,3 @@
-use std::collections::HashSet;
-
 use css_module_lexer::Dependency;
 use css_module_lexer::LexDependencies;
 use css_module_lexer::Lexer;
@@ -19,7 +17,6 @@ impl LocalByDefault {
         let mut warnings = Vec::new();
         let mut index = 0;
         let mut lexer = Lexer::new(input);
-        let mut local_alias = HashSet::new();
         let add_local = |result: &mut String, name: &str| {
             *result += ":local(";
             *result += name;
@@ -27,27 +24,13 @@ impl LocalByDefault {
         };
         let mut visitor = LexDependencies::new(
             |dependency| match dependency {
-                Dependency::LocalClass {
-                    name,
-                    range,
-                    explicit,
-                }
-                | Dependency::LocalId {
-                    name,
-                    range,
-                    explicit,
-                } => {
-                    if !explicit && local_alias.contains(&name[1..]) {
-                        return;
-                    }
+                Dependency::LocalClass { name, range, .. }
+                | Dependency::LocalId { name, range, .. } => {
                     result += Lexer::slice_range(input, &Range::new(index, range.start)).unwrap();
                     add_local(&mut result, name);
                     index = range.end;
                 }
                 Dependency::LocalKeyframes { name, range } => {
-                    if local_alias.contains(name) {
-                        return;
-                    }
                     result += Lexer::slice_range(input, &Range::new(index, range.start)).unwrap();
                     add_local(&mut result, name);
                     index = range.end;
@@ -66,9 +49,6 @@ impl LocalByDefault {
                     result += content;
                     index = range.end;
                 }
-                Dependency::ICSSImportValue { prop, .. } => {
-                    local_alias.insert(prop);
-                }
                 _ => {}
             },
             |warning| warnings.push(warning),
@@ -1,11 +1,36 @@
 mod postcss_modules;
 
+use std::fmt::Write;
+
 use css_module_lexer::collect_dependencies;
+use css_module_lexer::lex_dependencies_with_source_map;
+use css_module_lexer::Bundler;
 use css_module_lexer::Dependency;
+use css_module_lexer::Export;
+use css_module_lexer::ExportLocalsConvention;
+use css_module_lexer::ExportName;
+use css_module_lexer::InputSourceMap;
+use css_module_lexer::JsExports;
+use css_module_lexer::JsExportsFormat;
+use css_module_lexer::LexDependencies;
 use css_module_lexer::Lexer;
+use css_module_lexer::LocalIdentName;
+use css_module_lexer::LocalKind;
+use css_module_lexer::LocalName;
+use css_module_lexer::LocalScope;
 use css_module_lexer::Mode;
+use css_module_lexer::ModuleExports;
+use css_module_lexer::ModuleGraph;
+use css_module_lexer::ModuleWarning;
+use css_module_lexer::OriginalPosition;
+use css_module_lexer::Range;
+use css_module_lexer::ShortNames;
+use css_module_lexer::SourceMap;
+use css_module_lexer::Transformer;
 use css_module_lexer::UrlRangeKind;
+use css_module_lexer::UrlRewriter;
 use css_module_lexer::Warning;
+use css_module_lexer::WarningKind;
 use indoc::indoc;
 use smallvec::SmallVec;
 
@@ -190,6 +215,18 @@ fn assert_local_counter_style_dependency(input: &str, dependency: &Dependency, n
     assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
 }
 
+fn assert_local_counter_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalCounter {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
 fn assert_local_font_palette_decl_dependency(input: &str, dependency: &Dependency, name: &str) {
     let Dependency::LocalFontPaletteDecl {
         name: actual_name,
@@ -220,6 +257,207 @@ fn assert_local_font_palette_dependency(input: &str, dependency: &Dependency, na
     );
 }
 
+fn assert_local_grid_area_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalGridArea {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_local_grid_line_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalGridLine {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_local_container_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalContainer {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_local_container_decl_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalContainerDecl {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_local_anchor_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalAnchor {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{}", name)
+    );
+}
+
+fn assert_local_anchor_decl_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalAnchorDecl {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{}", name)
+    );
+}
+
+fn assert_local_position_try_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalPositionTry {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{}", name)
+    );
+}
+
+fn assert_local_position_try_decl_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalPositionTryDecl {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{}", name)
+    );
+}
+
+fn assert_local_timeline_dependency(
+    input: &str,
+    dependency: &Dependency,
+    name: &str,
+    from: Option<&str>,
+) {
+    let Dependency::LocalTimeline {
+        name: actual_name,
+        range,
+        from: actual_from,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(*actual_from, from);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{}", name)
+    );
+}
+
+fn assert_local_timeline_decl_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalTimelineDecl {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{}", name)
+    );
+}
+
+fn assert_local_view_transition_name_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalViewTransitionName {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_local_view_transition_name_decl_dependency(
+    input: &str,
+    dependency: &Dependency,
+    name: &str,
+) {
+    let Dependency::LocalViewTransitionNameDecl {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_local_view_transition_class_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::LocalViewTransitionClass {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_local_view_transition_class_decl_dependency(
+    input: &str,
+    dependency: &Dependency,
+    name: &str,
+) {
+    let Dependency::LocalViewTransitionClassDecl {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
 fn assert_composes_dependency(
     input: &str,
     dependency: &Dependency,
@@ -290,6 +528,18 @@ fn assert_icss_import_value_dependency(
     assert_eq!(*actual_value, value);
 }
 
+fn assert_icss_import_usage_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::ICSSImportUsage {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
 fn assert_icss_export_value_dependency(
     _input: &str,
     dependency: &Dependency,
@@ -307,52 +557,164 @@ fn assert_icss_export_value_dependency(
     assert_eq!(*actual_value, value);
 }
 
-#[test]
-fn empty() {
-    let (dependencies, warnings) = collect_dependencies("", Mode::Css);
-    assert!(warnings.is_empty());
-    assert!(dependencies.is_empty());
+fn assert_value_decl_dependency(_input: &str, dependency: &Dependency, name: &str, value: &str) {
+    let Dependency::ValueDecl {
+        name: actual_name,
+        value: actual_value,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(*actual_value, value);
 }
 
-#[test]
-fn url() {
-    let input = indoc! {r#"
-        body {
-            --a: url("./logo.png");
-            background: url(
-                https://example\2f4a8f.com\
-        /image.png
-            )
-        }
-    "#};
-    let (dependencies, warnings) = collect_dependencies(input, Mode::Css);
-    assert!(warnings.is_empty());
-    assert_url_dependency(
-        input,
-        &dependencies[0],
-        "./logo.png",
-        UrlRangeKind::String,
-        "\"./logo.png\"",
-    );
-    assert_url_dependency(
-        input,
-        &dependencies[1],
-        "https://example\\2f4a8f.com\\\n/image.png",
-        UrlRangeKind::Function,
-        "url(\n        https://example\\2f4a8f.com\\\n/image.png\n    )",
-    );
-    assert_eq!(dependencies.len(), 2);
+fn assert_value_import_dependency(
+    _input: &str,
+    dependency: &Dependency,
+    name: &str,
+    alias: &str,
+    from: &str,
+) {
+    let Dependency::ValueImport {
+        name: actual_name,
+        alias: actual_alias,
+        from: actual_from,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(*actual_alias, alias);
+    assert_eq!(*actual_from, from);
 }
 
-#[test]
-fn duplicate_url() {
-    let input = indoc! {r#"
-        @import url(./a.css) url(./a.css);
-        @import url(./a.css) url("./a.css");
-        @import url("./a.css") url(./a.css);
-        @import url("./a.css") url("./a.css");
-    "#};
-    let (_, warnings) = collect_dependencies(input, Mode::Css);
+fn assert_value_usage_dependency(input: &str, dependency: &Dependency, name: &str) {
+    let Dependency::ValueUsage {
+        name: actual_name,
+        range,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_custom_media_dependency(
+    input: &str,
+    dependency: &Dependency,
+    name: &str,
+    is_decl: bool,
+    is_local: bool,
+) {
+    let (actual_name, range, local) = match dependency {
+        Dependency::CustomMediaDecl { name, range, local } if is_decl => (name, range, local),
+        Dependency::CustomMedia { name, range, local } if !is_decl => (name, range, local),
+        _ => return assert!(false),
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(*local, is_local);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{name}")
+    );
+}
+
+fn assert_custom_selector_dependency(
+    input: &str,
+    dependency: &Dependency,
+    name: &str,
+    is_decl: bool,
+    is_local: bool,
+) {
+    let (actual_name, range, local) = match dependency {
+        Dependency::CustomSelectorDecl { name, range, local } if is_decl => (name, range, local),
+        Dependency::CustomSelector { name, range, local } if !is_decl => (name, range, local),
+        _ => return assert!(false),
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(*local, is_local);
+    assert_eq!(
+        Lexer::slice_range(input, range).unwrap(),
+        format!("--{name}")
+    );
+}
+
+fn assert_layer_name_dependency(input: &str, dependency: &Dependency, name: &str, is_local: bool) {
+    let Dependency::LayerName {
+        name: actual_name,
+        range,
+        local,
+    } = dependency
+    else {
+        return assert!(false);
+    };
+    assert_eq!(*actual_name, name);
+    assert_eq!(*local, is_local);
+    assert_eq!(Lexer::slice_range(input, range).unwrap(), name);
+}
+
+fn assert_scope_insertion_dependency(
+    input: &str,
+    dependency: &Dependency,
+    before: &str,
+    is_slotted: bool,
+) {
+    let Dependency::ScopeInsertion { range, slotted } = dependency else {
+        return assert!(false);
+    };
+    assert_eq!(*slotted, is_slotted);
+    assert_eq!(range.start, range.end);
+    assert!(input[..range.start as usize].ends_with(before));
+}
+
+#[test]
+fn empty() {
+    let (dependencies, warnings) = collect_dependencies("", Mode::Css);
+    assert!(warnings.is_empty());
+    assert!(dependencies.is_empty());
+}
+
+#[test]
+fn url() {
+    let input = indoc! {r#"
+        body {
+            --a: url("./logo.png");
+            background: url(
+                https://example\2f4a8f.com\
+        /image.png
+            )
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Css);
+    assert!(warnings.is_empty());
+    assert_url_dependency(
+        input,
+        &dependencies[0],
+        "./logo.png",
+        UrlRangeKind::String,
+        "\"./logo.png\"",
+    );
+    assert_url_dependency(
+        input,
+        &dependencies[1],
+        "https://example\\2f4a8f.com\\\n/image.png",
+        UrlRangeKind::Function,
+        "url(\n        https://example\\2f4a8f.com\\\n/image.png\n    )",
+    );
+    assert_eq!(dependencies.len(), 2);
+}
+
+#[test]
+fn duplicate_url() {
+    let input = indoc! {r#"
+        @import url(./a.css) url(./a.css);
+        @import url(./a.css) url("./a.css");
+        @import url("./a.css") url(./a.css);
+        @import url("./a.css") url("./a.css");
+    "#};
+    let (_, warnings) = collect_dependencies(input, Mode::Css);
     assert_warning(input, &warnings[0], "@import url(./a.css) url(./a.css)");
     assert_warning(input, &warnings[1], "@import url(./a.css) url(\"./a.css\"");
     assert_warning(input, &warnings[2], "@import url(\"./a.css\") url(./a.css)");
@@ -948,6 +1310,63 @@ fn css_modules_counter_style() {
     assert_local_counter_style_dependency(input, &dependencies[1], "circles");
 }
 
+#[test]
+fn css_modules_counter() {
+    let input = indoc! {r#"
+        .list {
+            counter-reset: section 1 list-item sub;
+            counter-increment: section;
+            counter-set: none;
+        }
+        .reversed {
+            counter-reset: reversed(d) 5 e;
+        }
+        .item::before {
+            content: counter(section, circles) ". " counters(sub, ".", upper-roman) counter(list-item);
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".list", false);
+    assert_local_counter_dependency(input, &dependencies[1], "section");
+    assert_local_counter_dependency(input, &dependencies[2], "sub");
+    assert_local_counter_dependency(input, &dependencies[3], "section");
+    assert_local_class_dependency(input, &dependencies[4], ".reversed", false);
+    assert_local_counter_dependency(input, &dependencies[5], "d");
+    assert_local_counter_dependency(input, &dependencies[6], "e");
+    assert_local_class_dependency(input, &dependencies[7], ".item", false);
+    assert_local_counter_dependency(input, &dependencies[8], "section");
+    assert_local_counter_style_dependency(input, &dependencies[9], "circles");
+    assert_local_counter_dependency(input, &dependencies[10], "sub");
+    assert_eq!(dependencies.len(), 11);
+}
+
+#[test]
+fn css_modules_counter_style_extends() {
+    let input = indoc! {r#"
+        @counter-style circles {
+            system: cyclic;
+            symbols: Ⓐ Ⓑ Ⓒ;
+        }
+        @counter-style parens {
+            system: extends circles;
+            fallback: circles;
+        }
+        @counter-style alpha {
+            system: extends decimal;
+            fallback: lower-alpha;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_counter_style_decl_dependency(input, &dependencies[0], "circles");
+    assert_local_counter_style_decl_dependency(input, &dependencies[1], "parens");
+    assert_local_counter_style_dependency(input, &dependencies[2], "circles");
+    assert_local_counter_style_dependency(input, &dependencies[3], "circles");
+    assert_local_counter_style_decl_dependency(input, &dependencies[4], "alpha");
+    assert_eq!(dependencies.len(), 5);
+}
+
 #[test]
 fn css_modules_font_palette() {
     let input = indoc! {r#"
@@ -968,6 +1387,288 @@ fn css_modules_font_palette() {
     assert_eq!(dependencies.len(), 3);
 }
 
+#[test]
+fn css_modules_grid_1() {
+    let input = indoc! {r#"
+        .grid {
+            grid-template-areas:
+                "head head"
+                "nav  main"
+                ".    foot";
+            grid-template-columns: [full-start] minmax(1em, 1fr) [main-start] minmax(0, 40em) [main-end] repeat(2, [col] 1fr) [full-end];
+        }
+        .head {
+            grid-area: head;
+        }
+        .nav {
+            grid-row: main-start / span 2 main-end;
+            grid-column: auto / col 2;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".grid", false);
+    assert_local_grid_area_dependency(input, &dependencies[1], "head");
+    assert_local_grid_area_dependency(input, &dependencies[2], "head");
+    assert_local_grid_area_dependency(input, &dependencies[3], "nav");
+    assert_local_grid_area_dependency(input, &dependencies[4], "main");
+    assert_local_grid_area_dependency(input, &dependencies[5], "foot");
+    assert_local_grid_line_dependency(input, &dependencies[6], "full");
+    assert_local_grid_line_dependency(input, &dependencies[7], "main");
+    assert_local_grid_line_dependency(input, &dependencies[8], "main");
+    assert_local_grid_line_dependency(input, &dependencies[9], "col");
+    assert_local_grid_line_dependency(input, &dependencies[10], "full");
+    assert_local_class_dependency(input, &dependencies[11], ".head", false);
+    assert_local_grid_line_dependency(input, &dependencies[12], "head");
+    assert_local_class_dependency(input, &dependencies[13], ".nav", false);
+    assert_local_grid_line_dependency(input, &dependencies[14], "main");
+    assert_local_grid_line_dependency(input, &dependencies[15], "main");
+    assert_local_grid_line_dependency(input, &dependencies[16], "col");
+    assert_eq!(dependencies.len(), 17);
+}
+
+#[test]
+fn css_modules_grid_2() {
+    let input = indoc! {r#"
+        .a {
+            grid: [header-top] "a a a" [header-bottom] [main-top] "b b b" 1fr [main-bottom] / auto 1fr auto;
+        }
+        :global .b {
+            grid-template-areas: "c d";
+            grid-area: inherit;
+        }
+        .c {
+            grid-column-start: var(--col);
+            display: grid;
+            --layout: grid "d";
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".a", false);
+    assert_local_grid_line_dependency(input, &dependencies[1], "header-top");
+    assert_local_grid_area_dependency(input, &dependencies[2], "a");
+    assert_local_grid_area_dependency(input, &dependencies[3], "a");
+    assert_local_grid_area_dependency(input, &dependencies[4], "a");
+    assert_local_grid_line_dependency(input, &dependencies[5], "header-bottom");
+    assert_local_grid_line_dependency(input, &dependencies[6], "main-top");
+    assert_local_grid_area_dependency(input, &dependencies[7], "b");
+    assert_local_grid_area_dependency(input, &dependencies[8], "b");
+    assert_local_grid_area_dependency(input, &dependencies[9], "b");
+    assert_local_grid_line_dependency(input, &dependencies[10], "main-bottom");
+    assert_replace_dependency(input, &dependencies[11], "", ":global ");
+    assert_local_class_dependency(input, &dependencies[12], ".c", false);
+    assert_local_var_dependency(input, &dependencies[13], "col", None);
+    assert_local_var_decl_dependency(input, &dependencies[14], "layout");
+    assert_eq!(dependencies.len(), 15);
+}
+
+#[test]
+fn css_modules_container_1() {
+    let input = indoc! {r#"
+        .card {
+            container-name: card sidebar;
+        }
+        .layout {
+            container: layout / inline-size;
+        }
+        .none {
+            container-name: none;
+        }
+        @container card (min-width: 400px) {
+            .title {
+                font-size: 1.5em;
+            }
+        }
+        @container (min-width: 400px) {}
+        @container style(--responsive: true) {}
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".card", false);
+    assert_local_container_decl_dependency(input, &dependencies[1], "card");
+    assert_local_container_decl_dependency(input, &dependencies[2], "sidebar");
+    assert_local_class_dependency(input, &dependencies[3], ".layout", false);
+    assert_local_container_decl_dependency(input, &dependencies[4], "layout");
+    assert_local_class_dependency(input, &dependencies[5], ".none", false);
+    assert_local_container_dependency(input, &dependencies[6], "card");
+    assert_local_class_dependency(input, &dependencies[7], ".title", false);
+    assert_eq!(dependencies.len(), 8);
+}
+
+#[test]
+fn css_modules_container_2() {
+    let input = indoc! {r#"
+        @container :global(card) (min-width: 400px) {}
+        @container :local(sidebar) (min-width: 400px) {}
+        :global .a {
+            container-name: card;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_replace_dependency(input, &dependencies[0], "", ":global(");
+    assert_replace_dependency(input, &dependencies[1], "", ")");
+    assert_replace_dependency(input, &dependencies[2], "", ":local(");
+    assert_local_container_dependency(input, &dependencies[3], "sidebar");
+    assert_replace_dependency(input, &dependencies[4], "", ")");
+    assert_replace_dependency(input, &dependencies[5], "", ":global ");
+    assert_eq!(dependencies.len(), 6);
+}
+
+#[test]
+fn css_modules_container_3() {
+    let input = indoc! {r#"
+        @container :local card (min-width: 400px) {}
+        @container :global sidebar (min-width: 400px) {}
+        @container a (min-width: 400px), b (max-width: 800px) {}
+        @keyframes :global fade {}
+        .a {
+            container-name: NONE;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_replace_dependency(input, &dependencies[0], "", ":local ");
+    assert_local_container_dependency(input, &dependencies[1], "card");
+    assert_replace_dependency(input, &dependencies[2], "", ":global ");
+    assert_local_container_dependency(input, &dependencies[3], "a");
+    assert_local_container_dependency(input, &dependencies[4], "b");
+    assert_replace_dependency(input, &dependencies[5], "", ":global ");
+    assert_local_class_dependency(input, &dependencies[6], ".a", false);
+    assert_eq!(dependencies.len(), 7);
+}
+
+#[test]
+fn css_modules_anchor() {
+    let input = indoc! {r#"
+        .anchor {
+            anchor-name: --tooltip, --menu;
+            anchor-scope: --tooltip;
+        }
+        .tooltip {
+            position-anchor: --tooltip;
+            top: anchor(--tooltip bottom);
+            left: anchor(center);
+            width: anchor-size(--menu width);
+            position-try-fallbacks: --custom-bottom, flip-block;
+        }
+        @position-try --custom-bottom {
+            top: anchor(bottom);
+            right: anchor(right --menu);
+            height: anchor-size(/* a */ height --tooltip, 10px);
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".anchor", false);
+    assert_local_anchor_decl_dependency(input, &dependencies[1], "tooltip");
+    assert_local_anchor_decl_dependency(input, &dependencies[2], "menu");
+    assert_local_anchor_dependency(input, &dependencies[3], "tooltip");
+    assert_local_class_dependency(input, &dependencies[4], ".tooltip", false);
+    assert_local_anchor_dependency(input, &dependencies[5], "tooltip");
+    assert_local_anchor_dependency(input, &dependencies[6], "tooltip");
+    assert_local_anchor_dependency(input, &dependencies[7], "menu");
+    assert_local_position_try_dependency(input, &dependencies[8], "custom-bottom");
+    assert_local_position_try_decl_dependency(input, &dependencies[9], "custom-bottom");
+    assert_local_anchor_dependency(input, &dependencies[10], "menu");
+    assert_local_anchor_dependency(input, &dependencies[11], "tooltip");
+    assert_eq!(dependencies.len(), 12);
+}
+
+#[test]
+fn css_modules_position_try_unexpected() {
+    let input = "@position-try custom {}";
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert_warning(input, &warnings[0], "cu");
+    assert_eq!(warnings.len(), 1);
+    assert!(dependencies.is_empty());
+}
+
+#[test]
+fn css_modules_timeline() {
+    let input = indoc! {r#"
+        .scroller {
+            scroll-timeline-name: --scroller;
+            scroll-timeline: --squares block;
+            view-timeline: --reveal inline;
+            view-timeline-name: --a, --b;
+        }
+        .parent {
+            timeline-scope: --reveal;
+        }
+        .animated {
+            animation-timeline: --scroller, scroll(root), --squares from "./timelines.css", --c from global;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".scroller", false);
+    assert_local_timeline_decl_dependency(input, &dependencies[1], "scroller");
+    assert_local_timeline_decl_dependency(input, &dependencies[2], "squares");
+    assert_local_timeline_decl_dependency(input, &dependencies[3], "reveal");
+    assert_local_timeline_decl_dependency(input, &dependencies[4], "a");
+    assert_local_timeline_decl_dependency(input, &dependencies[5], "b");
+    assert_local_class_dependency(input, &dependencies[6], ".parent", false);
+    assert_local_timeline_dependency(input, &dependencies[7], "reveal", None);
+    assert_local_class_dependency(input, &dependencies[8], ".animated", false);
+    assert_local_timeline_dependency(input, &dependencies[9], "scroller", None);
+    assert_local_timeline_dependency(
+        input,
+        &dependencies[10],
+        "squares",
+        Some("\"./timelines.css\""),
+    );
+    assert_local_timeline_dependency(input, &dependencies[11], "c", Some("global"));
+    assert_eq!(dependencies.len(), 12);
+}
+
+#[test]
+fn css_modules_view_transition() {
+    let input = indoc! {r#"
+        .hero {
+            view-transition-name: hero;
+            view-transition-class: card item;
+        }
+        .none {
+            view-transition-name: none;
+        }
+        ::view-transition-group(hero) {
+            animation-duration: 1s;
+        }
+        ::view-transition-old(*.card):only-child,
+        ::view-transition-new(hero.item) {}
+        ::view-transition-image-pair(root) {}
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".hero", false);
+    assert_local_view_transition_name_decl_dependency(input, &dependencies[1], "hero");
+    assert_local_view_transition_class_decl_dependency(input, &dependencies[2], "card");
+    assert_local_view_transition_class_decl_dependency(input, &dependencies[3], "item");
+    assert_local_class_dependency(input, &dependencies[4], ".none", false);
+    assert_local_view_transition_name_dependency(input, &dependencies[5], "hero");
+    assert_local_view_transition_class_dependency(input, &dependencies[6], "card");
+    assert_local_view_transition_name_dependency(input, &dependencies[7], "hero");
+    assert_local_view_transition_class_dependency(input, &dependencies[8], "item");
+    assert_eq!(dependencies.len(), 9);
+}
+
+#[test]
+fn css_modules_view_transition_global() {
+    let input = indoc! {r#"
+        :global ::view-transition-old(hero) {}
+        :global .a {
+            view-transition-name: hero;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_replace_dependency(input, &dependencies[0], "", ":global ");
+    assert_replace_dependency(input, &dependencies[1], "", ":global ");
+    assert_eq!(dependencies.len(), 2);
+}
+
 #[test]
 fn css_modules_keyframes_unexpected() {
     let input = indoc! {r#"
@@ -1093,8 +1794,10 @@ fn css_modules_at_rule_1() {
     "#};
     let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
     assert!(warnings.is_empty());
-    assert_local_class_dependency(input, &dependencies[0], ".class", false);
-    assert_eq!(dependencies.len(), 1);
+    assert_layer_name_dependency(input, &dependencies[0], "framework", false);
+    assert_layer_name_dependency(input, &dependencies[1], "container", false);
+    assert_local_class_dependency(input, &dependencies[2], ".class", false);
+    assert_eq!(dependencies.len(), 3);
 }
 
 #[test]
@@ -1470,3 +2173,1640 @@ fn icss_export() {
     );
     assert_eq!(dependencies.len(), 11);
 }
+
+#[test]
+fn css_modules_value_1() {
+    let input = indoc! {r#"
+        @value primary: #BF4040;
+        @value small (max-width: 599px);
+        @value empty:;
+        @value selector: .foo;
+        @media small {
+            .a selector {
+                color: primary;
+                border: 1px solid primary;
+            }
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_value_decl_dependency(input, &dependencies[0], "primary", "#BF4040");
+    assert_replace_dependency(input, &dependencies[1], "", "@value primary: #BF4040;");
+    assert_value_decl_dependency(input, &dependencies[2], "small", "(max-width: 599px)");
+    assert_replace_dependency(
+        input,
+        &dependencies[3],
+        "",
+        "@value small (max-width: 599px);",
+    );
+    assert_value_decl_dependency(input, &dependencies[4], "empty", "");
+    assert_replace_dependency(input, &dependencies[5], "", "@value empty:;");
+    assert_value_decl_dependency(input, &dependencies[6], "selector", ".foo");
+    assert_replace_dependency(input, &dependencies[7], "", "@value selector: .foo;");
+    assert_value_usage_dependency(input, &dependencies[8], "small");
+    assert_local_class_dependency(input, &dependencies[9], ".a", false);
+    assert_value_usage_dependency(input, &dependencies[10], "selector");
+    assert_value_usage_dependency(input, &dependencies[11], "primary");
+    assert_value_usage_dependency(input, &dependencies[12], "primary");
+    assert_eq!(dependencies.len(), 13);
+}
+
+#[test]
+fn css_modules_value_2() {
+    let input = indoc! {r#"
+        @value a, b as c from "./colors.css";
+        @value (d) from colors;
+        .a {
+            composes: b;
+            color: c;
+            c: b;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_value_import_dependency(input, &dependencies[0], "a", "a", "\"./colors.css\"");
+    assert_value_import_dependency(input, &dependencies[1], "b", "c", "\"./colors.css\"");
+    assert_replace_dependency(
+        input,
+        &dependencies[2],
+        "",
+        "@value a, b as c from \"./colors.css\";",
+    );
+    assert_value_import_dependency(input, &dependencies[3], "d", "d", "colors");
+    assert_replace_dependency(input, &dependencies[4], "", "@value (d) from colors;");
+    assert_local_class_dependency(input, &dependencies[5], ".a", false);
+    assert_composes_dependency(input, &dependencies[6], "a", "b", None, "b");
+    assert_replace_dependency(input, &dependencies[7], "", "composes: b;");
+    assert_value_usage_dependency(input, &dependencies[8], "c");
+    assert_eq!(dependencies.len(), 9);
+}
+
+#[test]
+fn css_modules_value_unexpected() {
+    let input = "@value a b c from './colors.css';\n@value :red;";
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert_warning(input, &warnings[0], "a b c from './colors.css'");
+    assert_warning(input, &warnings[1], ":red");
+    assert_eq!(warnings.len(), 2);
+    assert_replace_dependency(
+        input,
+        &dependencies[0],
+        "",
+        "@value a b c from './colors.css';",
+    );
+    assert_eq!(dependencies.len(), 1);
+}
+
+#[test]
+fn css_modules_custom_media_and_selector() {
+    let input = indoc! {r#"
+        @custom-media --small (max-width: 600px);
+        @custom-media --narrow (--small) and (orientation: portrait);
+        @custom-selector :--heading h1, .title;
+        .a { composes: b; }
+        @media (--narrow) {
+            :--heading.c, :is(:--heading) {}
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_custom_media_dependency(input, &dependencies[0], "small", true, false);
+    assert_custom_media_dependency(input, &dependencies[1], "narrow", true, false);
+    assert_custom_media_dependency(input, &dependencies[2], "small", false, false);
+    assert_custom_selector_dependency(input, &dependencies[3], "heading", true, false);
+    assert_local_class_dependency(input, &dependencies[4], ".title", false);
+    assert_local_class_dependency(input, &dependencies[5], ".a", false);
+    assert_composes_dependency(input, &dependencies[6], "a", "b", None, "b");
+    assert_replace_dependency(input, &dependencies[7], "", "composes: b;");
+    assert_custom_media_dependency(input, &dependencies[8], "narrow", false, false);
+    assert_custom_selector_dependency(input, &dependencies[9], "heading", false, false);
+    assert_local_class_dependency(input, &dependencies[10], ".c", false);
+    assert_custom_selector_dependency(input, &dependencies[11], "heading", false, false);
+    assert_eq!(dependencies.len(), 12);
+}
+
+#[test]
+fn css_modules_custom_media_and_selector_local_scope() {
+    let input = indoc! {r#"
+        @custom-media --small (max-width: 600px);
+        @custom-selector :--heading h1;
+        @media (--small) {
+            :--heading {}
+            :global :--heading {}
+        }
+    "#};
+    let mut dependencies = Vec::new();
+    let mut warnings = Vec::new();
+    let mut lexer = Lexer::new(input);
+    let mut visitor =
+        LexDependencies::new(|v| dependencies.push(v), |v| warnings.push(v), Mode::Local)
+            .with_local_scope(LocalScope {
+                custom_media: true,
+                custom_selector: true,
+                ..Default::default()
+            });
+    lexer.lex(&mut visitor);
+    assert!(warnings.is_empty());
+    assert_custom_media_dependency(input, &dependencies[0], "small", true, true);
+    assert_custom_selector_dependency(input, &dependencies[1], "heading", true, true);
+    assert_custom_media_dependency(input, &dependencies[2], "small", false, true);
+    assert_custom_selector_dependency(input, &dependencies[3], "heading", false, true);
+    assert_replace_dependency(input, &dependencies[4], "", ":global ");
+    assert_custom_selector_dependency(input, &dependencies[5], "heading", false, false);
+    assert_eq!(dependencies.len(), 6);
+}
+
+#[test]
+fn css_modules_custom_media_and_selector_unexpected() {
+    let input = "@custom-media small (max-width: 600px);\n@custom-selector --heading h1;";
+    let (_, warnings) = collect_dependencies(input, Mode::Local);
+    assert_warning(input, &warnings[0], "sm");
+    assert_warning(input, &warnings[1], "--");
+    assert_eq!(warnings.len(), 2);
+}
+
+#[test]
+fn layer_name() {
+    let input = indoc! {r#"
+        @import url("reset.css") layer( reset.base );
+        @layer a, b.c;
+        @layer d {
+            @layer e {}
+        }
+        @layer {}
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Css);
+    assert!(warnings.is_empty());
+    assert_import_dependency(
+        input,
+        &dependencies[0],
+        "reset.css",
+        Some(" reset.base "),
+        None,
+        None,
+        "@import url(\"reset.css\") layer( reset.base );",
+    );
+    assert_layer_name_dependency(input, &dependencies[1], "reset", false);
+    assert_layer_name_dependency(input, &dependencies[2], "base", false);
+    assert_layer_name_dependency(input, &dependencies[3], "a", false);
+    assert_layer_name_dependency(input, &dependencies[4], "b", false);
+    assert_layer_name_dependency(input, &dependencies[5], "c", false);
+    assert_layer_name_dependency(input, &dependencies[6], "d", false);
+    assert_layer_name_dependency(input, &dependencies[7], "e", false);
+    assert_eq!(dependencies.len(), 8);
+}
+
+#[test]
+fn css_modules_layer_name_local_scope() {
+    let input = indoc! {r#"
+        @import url("reset.css") layer(reset);
+        @layer base;
+        .a {
+            composes: b;
+        }
+        @layer components {
+            .c {
+                composes: d;
+            }
+        }
+    "#};
+    let mut dependencies = Vec::new();
+    let mut warnings = Vec::new();
+    let mut lexer = Lexer::new(input);
+    let mut visitor =
+        LexDependencies::new(|v| dependencies.push(v), |v| warnings.push(v), Mode::Local)
+            .with_local_scope(LocalScope {
+                layer: true,
+                ..Default::default()
+            });
+    lexer.lex(&mut visitor);
+    assert!(warnings.is_empty());
+    assert_layer_name_dependency(input, &dependencies[1], "reset", true);
+    assert_layer_name_dependency(input, &dependencies[2], "base", true);
+    assert_local_class_dependency(input, &dependencies[3], ".a", false);
+    assert_composes_dependency(input, &dependencies[4], "a", "b", None, "b");
+    assert_replace_dependency(input, &dependencies[5], "", "composes: b;");
+    assert_layer_name_dependency(input, &dependencies[6], "components", true);
+    assert_local_class_dependency(input, &dependencies[7], ".c", false);
+    assert_composes_dependency(input, &dependencies[8], "c", "d", None, "d");
+    assert_replace_dependency(input, &dependencies[9], "", "composes: d;");
+    assert_eq!(dependencies.len(), 10);
+}
+
+#[test]
+fn css_modules_supports_selector() {
+    let input = indoc! {r#"
+        @supports selector(.a:has(> .b, #c)) and (display: grid) {
+            .a {
+                composes: d;
+            }
+        }
+        @supports selector(:global(.e) .f) {}
+        @media screen, print {
+            .g {
+                composes: h;
+            }
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_local_class_dependency(input, &dependencies[0], ".a", false);
+    assert_local_class_dependency(input, &dependencies[1], ".b", false);
+    assert_local_id_dependency(input, &dependencies[2], "#c", false);
+    assert_local_class_dependency(input, &dependencies[3], ".a", false);
+    assert_composes_dependency(input, &dependencies[4], "a", "d", None, "d");
+    assert_replace_dependency(input, &dependencies[5], "", "composes: d;");
+    assert_replace_dependency(input, &dependencies[6], "", ":global(");
+    assert_replace_dependency(input, &dependencies[7], "", ")");
+    assert_local_class_dependency(input, &dependencies[8], ".f", false);
+    assert_local_class_dependency(input, &dependencies[9], ".g", false);
+    assert_composes_dependency(input, &dependencies[10], "g", "h", None, "h");
+    assert_replace_dependency(input, &dependencies[11], "", "composes: h;");
+    assert_eq!(dependencies.len(), 12);
+}
+
+#[test]
+fn icss_import_usage() {
+    let input = indoc! {r#"
+        :import("./colors.css") { i__blue: blue; i__a: a; }
+        .i__a > .b, :local(.i__a) {
+            color: i__blue;
+        }
+        :export {
+            blue: i__blue /* i__blue */;
+        }
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Local);
+    assert!(warnings.is_empty());
+    assert_icss_import_usage_dependency(input, &dependencies[4], "i__a");
+    assert_local_class_dependency(input, &dependencies[5], ".b", false);
+    assert_replace_dependency(input, &dependencies[6], "", ":local(");
+    assert_local_class_dependency(input, &dependencies[7], ".i__a", true);
+    assert_replace_dependency(input, &dependencies[8], "", ")");
+    assert_icss_import_usage_dependency(input, &dependencies[9], "i__blue");
+    assert_icss_export_value_dependency(input, &dependencies[10], "blue", "i__blue /* i__blue */");
+    assert_icss_import_usage_dependency(input, &dependencies[11], "i__blue");
+    assert_replace_dependency(
+        input,
+        &dependencies[12],
+        "",
+        ":export {\n    blue: i__blue /* i__blue */;\n}",
+    );
+    assert_eq!(dependencies.len(), 13);
+}
+
+fn transform(input: &str, mode: Mode) -> String {
+    let mut output = String::new();
+    let warnings = Transformer::new(input, mode, |local_name: &LocalName| {
+        format!("{:?}_{}", local_name.kind, local_name.name)
+    })
+    .transform(&mut output)
+    .unwrap();
+    assert!(warnings.is_empty(), "{}", &warnings[0]);
+    output
+}
+
+#[test]
+fn transform_local() {
+    let input = indoc! {r#"
+        @value primary: red;
+        @keyframes fade {}
+        @property --x { syntax: "*"; }
+        .e {
+            composes: f;
+            color: primary;
+            animation: 1s fade var(--y);
+            --x: 1;
+            --z: var(--x from "./vars.css");
+        }
+        :global(.g) .a:local(#b), :global .c .d {}
+    "#};
+    similar_asserts::assert_eq!(
+        transform(input, Mode::Local),
+        indoc! {r#"
+            
+            @keyframes Keyframes_fade {}
+            @property --Var_x { syntax: "*"; }
+            .Class_e {
+                
+                color: red;
+                animation: 1s Keyframes_fade var(--Var_y);
+                --Var_x: 1;
+                --Var_z: var(--Var_x);
+            }
+            .g .Class_a#Id_b, .c .d {}
+        "#}
+    );
+}
+
+#[test]
+fn transform_global() {
+    let input = ".a :local(.b) { animation: fade; }";
+    similar_asserts::assert_eq!(
+        transform(input, Mode::Global),
+        ".a .Class_b { animation: fade; }"
+    );
+    similar_asserts::assert_eq!(transform(input, Mode::Css), input);
+}
+
+#[test]
+fn transform_source_map() {
+    let input = ".a {}\n.b { color: \"\\\"\"; }";
+    let mut output = String::new();
+    let (warnings, source_map) = Transformer::new(input, Mode::Local, |local_name: &LocalName| {
+        format!("_{}", local_name.name)
+    })
+    .transform_with_source_map(&mut output, "input.css")
+    .unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(output, "._a {}\n._b { color: \"\\\"\"; }");
+    assert_eq!(source_map.names, vec!["a", "b"]);
+    assert_eq!(source_map.mappings, "AAAAA,GAAE;AACFC,GAAE");
+    assert_eq!(
+        source_map.to_string(),
+        r#"{"version":3,"sources":["input.css"],"sourcesContent":[".a {}\n.b { color: \"\\\"\"; }"],"names":["a","b"],"mappings":"AAAAA,GAAE;AACFC,GAAE"}"#
+    );
+}
+
+#[test]
+fn input_source_map() {
+    let input = "\n:global .foo, .bar {}";
+    let source_map = SourceMap::from_json(
+        r#"{"version":3,"sourceRoot":"src/","sources":["a.scss"],"names":[],"mappings":";AAIE"}"#,
+    )
+    .unwrap();
+    assert_eq!(source_map.sources, vec!["src/a.scss"]);
+    let mut dependencies = Vec::new();
+    let mut warnings = Vec::new();
+    lex_dependencies_with_source_map(
+        input,
+        Mode::Local,
+        &source_map,
+        |dependency, original| dependencies.push((dependency, original)),
+        |warning, original| warnings.push((warning, original)),
+    );
+    let (warning, original) = &warnings[0];
+    assert!(matches!(
+        warning.kind(),
+        WarningKind::InconsistentModeResult
+    ));
+    assert_eq!(original.unwrap().start.to_string(), "src/a.scss:5:15");
+    assert_eq!(warnings.len(), 1);
+    let (dependency, original) = &dependencies[1];
+    assert_local_class_dependency(input, dependency, ".bar", false);
+    assert_eq!(
+        original.unwrap().start,
+        OriginalPosition {
+            source: "src/a.scss",
+            line: 4,
+            column: 16,
+        }
+    );
+}
+
+#[test]
+fn input_source_map_range() {
+    let input = ".foo .bar {}";
+    let source_map =
+        SourceMap::from_json(r#"{"version":3,"sources":["a.scss"],"names":[],"mappings":"AAAA"}"#)
+            .unwrap();
+    let input_source_map = InputSourceMap::new(&source_map, input);
+    let original = |start, end| {
+        let range = input_source_map
+            .original_range(&Range::new(start, end))
+            .unwrap();
+        format!("{}-{}", range.start, range.end)
+    };
+    assert_eq!(original(0, 4), "a.scss:1:1-a.scss:1:5");
+    assert_eq!(original(5, 9), "a.scss:1:6-a.scss:1:10");
+}
+
+#[test]
+fn input_source_map_malformed_mappings() {
+    let input = ".a {}";
+    let source_map = SourceMap::from_json(
+        r#"{"version":3,"sources":["a.scss"],"names":[],"mappings":"AAAA,gggggggggggggggggA"}"#,
+    )
+    .unwrap();
+    let mut dependencies = Vec::new();
+    lex_dependencies_with_source_map(
+        input,
+        Mode::Local,
+        &source_map,
+        |dependency, original| dependencies.push((dependency, original)),
+        |_, _| {},
+    );
+    let (dependency, original) = &dependencies[0];
+    assert_local_class_dependency(input, dependency, ".a", false);
+    assert_eq!(original.unwrap().start.to_string(), "a.scss:1:1");
+}
+
+#[test]
+fn input_source_map_round_trip() {
+    let input = "/* a */\n.a {}\n  .b {}";
+    let mut output = String::new();
+    let (_, source_map) = Transformer::new(input, Mode::Local, |local_name: &LocalName| {
+        format!("_{}", local_name.name)
+    })
+    .transform_with_source_map(&mut output, "input.css")
+    .unwrap();
+    let source_map = SourceMap::from_json(&source_map.to_string()).unwrap();
+    let input_source_map = InputSourceMap::new(&source_map, &output);
+    let original = input_source_map.original_position(3).unwrap();
+    assert_eq!(original.to_string(), "input.css:1:4");
+    let original = input_source_map
+        .original_position(output.find("._b").unwrap() as u32)
+        .unwrap();
+    assert_eq!(original.to_string(), "input.css:3:3");
+}
+
+#[test]
+fn local_ident_name() {
+    let ident = LocalIdentName::new("[hash:base64:5]").with_context("/project");
+    assert_eq!(
+        ident.generate("/project/src/button.css", "primary"),
+        "v_tDB"
+    );
+    let ident = LocalIdentName::new("[hash]").with_context("/project");
+    assert_eq!(
+        ident.generate("/project/src/button.css", "primary"),
+        "bffb43047c3f9eed7dc5"
+    );
+    let ident = LocalIdentName::new("[md4:contenthash:hex:40]").with_context("/project");
+    assert_eq!(
+        ident.generate("/project/src/button.css", "primary"),
+        "bffb43047c3f9eed7dc5700d4e2b1e89d1cf966f"
+    );
+    let ident = LocalIdentName::new("[hash:base64:8]")
+        .with_context("/project")
+        .with_hash_salt("salt");
+    assert_eq!(
+        ident.generate("/project/src/button.css", "primary"),
+        "PUzx0k4j"
+    );
+    let ident = LocalIdentName::new("[hash:base64:5]-[hash:hex:12]").with_context("/project");
+    assert_eq!(
+        ident.generate("/project/src/button.css", "primary"),
+        "v_tDB-bffb43047c3f"
+    );
+}
+
+#[test]
+fn local_ident_name_placeholders() {
+    let ident = LocalIdentName::new("[path][name]__[local]").with_context("/project");
+    assert_eq!(
+        ident.generate("/project/src/button.css", "primary"),
+        "src-button__primary"
+    );
+    let ident = LocalIdentName::new("[folder]_[name][ext]_[local]").with_context("/project");
+    assert_eq!(
+        ident.generate("/project/src/button.module.css", "a"),
+        "src_button-module-css_a"
+    );
+    let ident = LocalIdentName::new("[local]-[unknown]");
+    assert_eq!(ident.generate("a.css", "a"), "a-\\[unknown\\]");
+    let ident = LocalIdentName::new("[name]").with_context("C:\\project\\src");
+    assert_eq!(ident.generate("C:\\project\\1.css", "a"), "_1");
+    let ident = LocalIdentName::new("[path][local]").with_context("/project/src");
+    assert_eq!(ident.generate("/project/lib/a.css", "a"), "\\---lib-a");
+}
+
+#[test]
+fn transform_local_ident_name() {
+    let input = ".a { animation: fade; } @keyframes fade {}";
+    let ident = LocalIdentName::new("[name]__[local]").with_context("/project");
+    let mut output = String::new();
+    Transformer::new(input, Mode::Local, |local_name: &LocalName| {
+        ident.generate("/project/button.css", local_name.name)
+    })
+    .transform(&mut output)
+    .unwrap();
+    similar_asserts::assert_eq!(
+        output,
+        ".button__a { animation: button__fade; } @keyframes button__fade {}"
+    );
+}
+
+fn module_exports(input: &str, mode: Mode) -> Vec<Export> {
+    let mut exports = ModuleExports::new(|local_name: &LocalName| {
+        format!("{:?}_{}", local_name.kind, local_name.name)
+    });
+    let mut warnings = Vec::new();
+    let mut lexer = Lexer::new(input);
+    let mut visitor = LexDependencies::new(
+        |dependency| exports.add(&dependency),
+        |warning| warnings.push(warning),
+        mode,
+    );
+    lexer.lex(&mut visitor);
+    assert!(warnings.is_empty(), "{}", &warnings[0]);
+    exports.into_exports()
+}
+
+fn assert_export(export: &Export, name: &str, values: &[ExportName]) {
+    assert_eq!(export.name, name);
+    assert_eq!(export.values, values);
+}
+
+#[test]
+fn module_exports_composes() {
+    let input = indoc! {r#"
+        .a { color: red; }
+        .b { composes: a; }
+        .c {
+            composes: b d;
+            composes: e from global;
+            composes: f g from "./file.css";
+        }
+        .d {}
+    "#};
+    let exports = module_exports(input, Mode::Local);
+    assert_export(&exports[0], "a", &[ExportName::Local("Class_a".into())]);
+    assert_export(
+        &exports[1],
+        "b",
+        &[
+            ExportName::Local("Class_b".into()),
+            ExportName::Local("Class_a".into()),
+        ],
+    );
+    assert_export(
+        &exports[2],
+        "c",
+        &[
+            ExportName::Local("Class_c".into()),
+            ExportName::Local("Class_b".into()),
+            ExportName::Local("Class_a".into()),
+            ExportName::Local("Class_d".into()),
+            ExportName::Global("e"),
+            ExportName::Import {
+                name: "f",
+                from: "\"./file.css\"",
+            },
+            ExportName::Import {
+                name: "g",
+                from: "\"./file.css\"",
+            },
+        ],
+    );
+    assert_export(&exports[3], "d", &[ExportName::Local("Class_d".into())]);
+    assert_eq!(exports.len(), 4);
+}
+
+#[test]
+fn module_exports_locals() {
+    let input = indoc! {r#"
+        @value primary: red;
+        @value secondary: primary;
+        @value tertiary from "./colors.css";
+        @keyframes fade {}
+        .a, #b { animation: fade; --x: var(--y from "./vars.css"); }
+        .a {}
+        :export { a: A; primary: blue }
+    "#};
+    let exports = module_exports(input, Mode::Local);
+    assert_export(
+        &exports[0],
+        "primary",
+        &[ExportName::Global("red"), ExportName::Global("blue")],
+    );
+    assert_export(&exports[1], "secondary", &[ExportName::Global("red")]);
+    assert_export(
+        &exports[2],
+        "tertiary",
+        &[ExportName::Import {
+            name: "tertiary",
+            from: "\"./colors.css\"",
+        }],
+    );
+    assert_export(
+        &exports[3],
+        "fade",
+        &[ExportName::Local("Keyframes_fade".into())],
+    );
+    assert_export(
+        &exports[4],
+        "a",
+        &[ExportName::Local("Class_a".into()), ExportName::Global("A")],
+    );
+    assert_export(&exports[5], "b", &[ExportName::Local("Id_b".into())]);
+    assert_export(&exports[6], "--x", &[ExportName::Local("--Var_x".into())]);
+    assert_eq!(exports.len(), 7);
+}
+
+#[test]
+fn module_exports_kinds() {
+    let input = ".head { grid-area: head; counter-reset: head; container-name: head; }";
+    let exports = module_exports(input, Mode::Local);
+    assert_export(
+        &exports[0],
+        "head",
+        &[ExportName::Local("Class_head".into())],
+    );
+    assert_eq!(exports.len(), 1);
+}
+
+fn js_exports(exports: &[Export], js_exports: JsExports) -> String {
+    let mut output = String::new();
+    js_exports.write(exports, &mut output).unwrap();
+    output
+}
+
+#[test]
+fn js_exports_format() {
+    let input = indoc! {r#"
+        .a { composes: b from global; }
+        .b-c { composes: d e from "./file.css"; composes: a; }
+        :export { default: "x" }
+    "#};
+    let exports = module_exports(input, Mode::Local);
+    similar_asserts::assert_eq!(
+        js_exports(&exports, JsExports::new(JsExportsFormat::Named)),
+        indoc! {r#"
+            import * as __css_import_0__ from "./file.css";
+            export var a = "Class_a b";
+            var __css_export_1__ = "Class_b-c " + __css_import_0__["d"] + " " + __css_import_0__["e"] + " Class_a b";
+            export { __css_export_1__ as "b-c" };
+            export var _default = "\"x\"";
+        "#}
+    );
+    similar_asserts::assert_eq!(
+        js_exports(&exports, JsExports::new(JsExportsFormat::Default)),
+        indoc! {r#"
+            import __css_import_0__ from "./file.css";
+            export default {
+              a: "Class_a b",
+              "b-c": "Class_b-c " + __css_import_0__["d"] + " " + __css_import_0__["e"] + " Class_a b",
+              default: "\"x\"",
+            };
+        "#}
+    );
+    similar_asserts::assert_eq!(
+        js_exports(&exports, JsExports::new(JsExportsFormat::CommonJs)),
+        indoc! {r#"
+            var __css_import_0__ = require("./file.css");
+            module.exports = {
+              a: "Class_a b",
+              "b-c": "Class_b-c " + __css_import_0__["d"] + " " + __css_import_0__["e"] + " Class_a b",
+              default: "\"x\"",
+            };
+        "#}
+    );
+    similar_asserts::assert_eq!(
+        js_exports(&[], JsExports::new(JsExportsFormat::CommonJs)),
+        "module.exports = {};\n"
+    );
+}
+
+#[test]
+fn js_exports_locals_convention() {
+    let names = [
+        ("a-b_c", "aBC", "aB_c"),
+        ("fooBar", "fooBar", "fooBar"),
+        ("FOOBar", "fooBar", "FOOBar"),
+        ("Foo-Bar", "fooBar", "FooBar"),
+        ("__foo", "foo", "__foo"),
+        ("a1_b", "a1B", "a1_b"),
+        ("btn-primary-2x", "btnPrimary2X", "btnPrimary2x"),
+        ("XMLHttpRequest", "xmlHttpRequest", "XMLHttpRequest"),
+        ("a--b-", "aB", "aB-"),
+        ("a.b c", "aBC", "a.b c"),
+    ];
+    for (name, camel_case, dashes) in names {
+        let exports = [Export {
+            name: name.into(),
+            values: vec![ExportName::Global("x")],
+        }];
+        let convention = |convention| {
+            JsExports::new(JsExportsFormat::Default).with_export_locals_convention(convention)
+        };
+        let output = |names: &[&str]| {
+            let mut output = String::from("export default {\n");
+            for name in names {
+                if name.contains(['-', '.', ' ']) {
+                    output += &format!("  \"{name}\": \"x\",\n");
+                } else {
+                    output += &format!("  {name}: \"x\",\n");
+                }
+            }
+            output + "};\n"
+        };
+        let mut both = vec![name];
+        if camel_case != name {
+            both.push(camel_case);
+        }
+        similar_asserts::assert_eq!(
+            js_exports(&exports, convention(ExportLocalsConvention::CamelCase)),
+            output(&both)
+        );
+        similar_asserts::assert_eq!(
+            js_exports(&exports, convention(ExportLocalsConvention::CamelCaseOnly)),
+            output(&[camel_case])
+        );
+        let mut both = vec![name];
+        if dashes != name {
+            both.push(dashes);
+        }
+        similar_asserts::assert_eq!(
+            js_exports(&exports, convention(ExportLocalsConvention::Dashes)),
+            output(&both)
+        );
+        similar_asserts::assert_eq!(
+            js_exports(&exports, convention(ExportLocalsConvention::DashesOnly)),
+            output(&[dashes])
+        );
+        similar_asserts::assert_eq!(
+            js_exports(&exports, convention(ExportLocalsConvention::AsIs)),
+            output(&[name])
+        );
+    }
+}
+
+#[test]
+fn js_exports_escape() {
+    let exports = [
+        Export {
+            name: "a\"\\\n\u{2028}".into(),
+            values: vec![ExportName::Global("\"x\"")],
+        },
+        Export {
+            name: "class".into(),
+            values: vec![ExportName::Import {
+                name: "a-b",
+                from: "'./a\".css'",
+            }],
+        },
+    ];
+    similar_asserts::assert_eq!(
+        js_exports(
+            &exports,
+            JsExports::new(JsExportsFormat::Named)
+                .with_export_locals_convention(ExportLocalsConvention::DashesOnly)
+        ),
+        indoc! {r#"
+            import * as __css_import_0__ from "./a\".css";
+            var __css_export_0__ = "\"x\"";
+            export { __css_export_0__ as "a\"\\\n\u2028" };
+            var __css_export_1__ = __css_import_0__["aB"];
+            export { __css_export_1__ as "class" };
+        "#}
+    );
+}
+
+#[test]
+fn js_exports_named_default() {
+    let input = indoc! {r#"
+        .default { composes: default from "./file.css"; }
+        .a {}
+    "#};
+    let exports = module_exports(input, Mode::Local);
+    similar_asserts::assert_eq!(
+        js_exports(&exports, JsExports::new(JsExportsFormat::Named)),
+        indoc! {r#"
+            import * as __css_import_0__ from "./file.css";
+            export var _default = "Class_default " + __css_import_0__["_default"];
+            export var a = "Class_a";
+        "#}
+    );
+    similar_asserts::assert_eq!(
+        js_exports(&exports, JsExports::new(JsExportsFormat::Default)),
+        indoc! {r#"
+            import __css_import_0__ from "./file.css";
+            export default {
+              default: "Class_default " + __css_import_0__["default"],
+              a: "Class_a",
+            };
+        "#}
+    );
+}
+
+fn dts(exports: &[Export], js_exports: JsExports) -> String {
+    let mut output = String::new();
+    js_exports.write_dts(exports, &mut output).unwrap();
+    output
+}
+
+#[test]
+fn js_exports_dts() {
+    let input = indoc! {r#"
+        .b-c { composes: d from "./file.css"; }
+        .a {}
+        :export { default: x; z: y }
+    "#};
+    let exports = module_exports(input, Mode::Local);
+    similar_asserts::assert_eq!(
+        dts(&exports, JsExports::new(JsExportsFormat::Named)),
+        indoc! {r#"
+            export declare const _default: string;
+            export declare const a: string;
+            declare const __css_export_2__: string;
+            export { __css_export_2__ as "b-c" };
+            export declare const z: string;
+        "#}
+    );
+    similar_asserts::assert_eq!(
+        dts(
+            &exports,
+            JsExports::new(JsExportsFormat::Default)
+                .with_export_locals_convention(ExportLocalsConvention::CamelCase)
+        ),
+        indoc! {r#"
+            declare const styles: {
+              readonly a: string;
+              readonly "b-c": string;
+              readonly bC: string;
+              readonly default: string;
+              readonly z: string;
+            };
+            export default styles;
+        "#}
+    );
+    similar_asserts::assert_eq!(
+        dts(&[], JsExports::new(JsExportsFormat::CommonJs)),
+        "declare const styles: {};\nexport = styles;\n"
+    );
+}
+
+fn transform_icss(input: &str) -> (String, Vec<Warning>) {
+    let mut output = String::new();
+    let warnings = Transformer::new(input, Mode::Local, |local_name: &LocalName| {
+        format!("{:?}_{}", local_name.kind, local_name.name)
+    })
+    .transform_icss(&mut output)
+    .unwrap();
+    (output, warnings)
+}
+
+#[test]
+fn transform_icss_imports() {
+    let input = indoc! {r#"
+        :import("./bb.css") {
+            somevalue: localvalue;
+        }
+        @value primary, secondary as sec from "./colors.css";
+        .a {
+            composes: aa from './aa.css';
+            color: primary;
+        }
+        .b {
+            composes: bb from './bb.css';
+            composes: bb from './aa.css';
+            composes: g from global;
+        }
+        .c {
+            composes: cc from './cc.css';
+            composes: cc from './aa.css';
+            composes: a;
+        }
+        .d {
+            composes: dd from './cc.css';
+            composes: dd from './bb.css';
+            color: var(--x from "./dd.css");
+        }
+        :export { e: somevalue }
+    "#};
+    let (output, warnings) = transform_icss(input);
+    assert!(warnings.is_empty(), "{}", &warnings[0]);
+    similar_asserts::assert_eq!(
+        output,
+        indoc! {r#"
+            :import("./cc.css") {
+                i__imported_cc_3: cc;
+                i__imported_dd_5: dd;
+            }
+            :import("./bb.css") {
+                somevalue: localvalue;
+                i__imported_bb_1: bb;
+                i__imported_dd_6: dd;
+            }
+            :import("./colors.css") {
+                primary: primary;
+                sec: secondary;
+            }
+            :import("./aa.css") {
+                i__imported_aa_0: aa;
+                i__imported_bb_2: bb;
+                i__imported_cc_4: cc;
+            }
+            :import("./dd.css") {
+                --i__imported_x_7: --x;
+            }
+
+
+            .Class_a {
+                
+                color: primary;
+            }
+            .Class_b {
+                
+                
+                
+            }
+            .Class_c {
+                
+                
+                
+            }
+            .Class_d {
+                
+                
+                color: var(--i__imported_x_7);
+            }
+
+            :export {
+                primary: primary;
+                sec: sec;
+                a: Class_a i__imported_aa_0;
+                b: Class_b i__imported_bb_1 i__imported_bb_2 g;
+                c: Class_c i__imported_cc_3 i__imported_cc_4 Class_a i__imported_aa_0;
+                d: Class_d i__imported_dd_5 i__imported_dd_6;
+                e: somevalue;
+            }
+        "#}
+    );
+}
+
+#[test]
+fn transform_icss_import_order() {
+    let input = indoc! {r#"
+        .aa {
+            composes: b from './b.css';
+            composes: c from './c.css';
+        }
+        .bb {
+            composes: c from './c.css';
+            composes: b from './b.css';
+        }
+    "#};
+    let (output, warnings) = transform_icss(input);
+    similar_asserts::assert_eq!(
+        output,
+        indoc! {r#"
+            :import("./c.css") {
+                i__imported_c_1: c;
+            }
+            :import("./b.css") {
+                i__imported_b_0: b;
+            }
+            .Class_aa {
+                
+                
+            }
+            .Class_bb {
+                
+                
+            }
+            :export {
+                aa: Class_aa i__imported_b_0 i__imported_c_1;
+                bb: Class_bb i__imported_c_1 i__imported_b_0;
+            }
+        "#}
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "Failed to resolve order of composed modules"
+    );
+    assert_warning(input, &warnings[0], "b from './b.css'");
+    assert_eq!(warnings.len(), 1);
+}
+
+fn module_graph<'a>(
+    files: &'a [(&'a str, &'a str)],
+) -> ModuleGraph<
+    impl FnMut(&str, &str) -> Option<String>,
+    impl FnMut(&str) -> Option<String> + 'a,
+    impl FnMut(&str, &LocalName) -> String,
+> {
+    ModuleGraph::new(
+        Mode::Local,
+        |path: &str, request: &str| {
+            let dir = &path[..path.rfind('/').unwrap()];
+            Some(format!("{dir}/{}", request.trim_start_matches("./")))
+        },
+        |path: &str| {
+            files
+                .iter()
+                .find(|(file, _)| *file == path)
+                .map(|(_, source)| source.to_string())
+        },
+        |path: &str, local_name: &LocalName| {
+            let file = &path[path.rfind('/').unwrap() + 1..path.rfind('.').unwrap()];
+            format!("{file}_{}", local_name.name)
+        },
+    )
+}
+
+#[test]
+fn module_graph_exports() {
+    let files = [
+        (
+            "/src/a.css",
+            indoc! {r#"
+                :import("./b.css") { i__b: b; }
+                @value v from "./b.css";
+                .a {
+                    composes: b from "./b.css";
+                    color: var(--x from "./b.css");
+                }
+                .e { composes: a; composes: c from "./c.css"; }
+                :export { x: i__b }
+            "#},
+        ),
+        (
+            "/src/b.css",
+            indoc! {r#"
+                @value v: blue;
+                .b { composes: c from './c.css'; --x: red; }
+            "#},
+        ),
+        ("/src/c.css", ".c {}"),
+    ];
+    let mut graph = module_graph(&files);
+    let a = graph.add("/src/a.css").unwrap();
+    assert!(graph.warnings().is_empty(), "{}", &graph.warnings()[0]);
+    let b = graph.get("/src/b.css").unwrap();
+    let c = graph.get("/src/c.css").unwrap();
+    assert_eq!(graph.order(), [c, b, a]);
+    assert_eq!(graph.module(a).dependencies().collect::<Vec<_>>(), [b, c]);
+    assert_eq!(
+        graph.module(a).exports().collect::<Vec<_>>(),
+        [
+            ("v", &["blue".to_string()][..]),
+            ("a", &["a_a".into(), "b_b".into(), "c_c".into()]),
+            (
+                "e",
+                &["a_e".into(), "a_a".into(), "b_b".into(), "c_c".into()]
+            ),
+            ("x", &["b_b".into(), "c_c".into()]),
+        ]
+    );
+    assert_eq!(graph.imported(a, "\"./b.css\"", "--x").unwrap(), ["--b_x"]);
+    assert_eq!(graph.imported(a, "./c.css", "c").unwrap(), ["c_c"]);
+    assert_eq!(graph.imported(a, "./c.css", "d"), None);
+}
+
+#[test]
+fn module_graph_warnings() {
+    let files = [
+        (
+            "/a.css",
+            indoc! {r#"
+                .a { composes: b from "./b.css"; }
+                .c { composes: d from "./missing.css"; }
+            "#},
+        ),
+        (
+            "/b.css",
+            ".b { composes: a from './a.css'; composes: x from './c.css'; }",
+        ),
+        ("/c.css", ".c {}"),
+    ];
+    let mut graph = module_graph(&files);
+    let a = graph.add("/a.css").unwrap();
+    let b = graph.get("/b.css").unwrap();
+    let c = graph.get("/c.css").unwrap();
+    assert_eq!(graph.order(), [c, b, a]);
+    let warnings = graph.warnings();
+    assert_eq!(
+        warnings[0].to_string(),
+        "/b.css: Circular import of '/a.css'"
+    );
+    assert_eq!(
+        Lexer::slice_range(files[1].1, &warnings[0].range).unwrap(),
+        "a from './a.css'"
+    );
+    assert_eq!(
+        warnings[1].to_string(),
+        "/b.css: Can't find 'x' in './c.css'"
+    );
+    assert_eq!(
+        Lexer::slice_range(files[1].1, &warnings[1].range).unwrap(),
+        "x"
+    );
+    assert_eq!(warnings[2].to_string(), "/a.css: Can't load '/missing.css'");
+    assert_eq!(
+        Lexer::slice_range(files[0].1, &warnings[2].range).unwrap(),
+        "d from \"./missing.css\""
+    );
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(graph.module(a).export("a").unwrap(), ["a_a", "b_b"]);
+    assert_eq!(graph.module(b).export("b").unwrap(), ["b_b"]);
+}
+
+fn bundle(files: &[(&str, &str)], path: &str) -> (String, Vec<ModuleWarning>) {
+    let mut bundler = Bundler::new(
+        |_: &str, request: &str| {
+            (!request.starts_with("https:") && request != "missing.css")
+                .then(|| format!("/{}", request.trim_start_matches("./")))
+        },
+        |path: &str| {
+            files
+                .iter()
+                .find(|(file, _)| *file == path)
+                .map(|(_, source)| source.to_string())
+        },
+    );
+    let mut output = String::new();
+    let warnings = bundler.bundle(path, &mut output).unwrap();
+    (output, warnings)
+}
+
+#[test]
+fn bundle_import() {
+    let files = [
+        (
+            "/index.css",
+            indoc! {r#"
+                @import "https://example.com/a.css";
+                @import "./a.css" layer(base.reset) supports(display: grid) screen and (min-width: 100px);
+                @import url(b.css) layer;
+                @import "b.css" layer;
+                @import "b.css" print;
+                .index {}
+            "#},
+        ),
+        ("/a.css", "@import 'b.css';\n.a {}\n"),
+        ("/b.css", ".b {}"),
+    ];
+    let (output, warnings) = bundle(&files, "/index.css");
+    similar_asserts::assert_eq!(
+        output,
+        indoc! {r#"
+            @import "https://example.com/a.css";
+
+            @media screen and (min-width: 100px) {
+            @supports (display: grid) {
+            @layer base.reset {
+            .b {}
+            .a {}
+            }
+            }
+            }
+
+            @layer {
+            .b {}
+            }
+
+
+            @media print {
+            .b {}
+            }
+
+            .index {}
+        "#}
+    );
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn bundle_import_hoisted() {
+    let files = [
+        (
+            "/index.css",
+            indoc! {r#"
+                @charset "utf-8";
+                @import "x.css" screen;
+                @import "https://example.com/index.css";
+                @import "missing.css";
+                .index {}
+            "#},
+        ),
+        (
+            "/x.css",
+            indoc! {r#"
+                @charset "utf-8";
+                @import url("https://example.com/x.css") layer(x) supports(display: grid) print, (min-width: 100px);
+                .x {}
+            "#},
+        ),
+    ];
+    let (output, warnings) = bundle(&files, "/index.css");
+    similar_asserts::assert_eq!(
+        output,
+        indoc! {r#"
+            @charset "utf-8";
+            @import "https://example.com/x.css" layer(x) supports((display: grid)) screen and print, screen and (min-width: 100px);
+            @import "https://example.com/index.css";
+            @import "missing.css";
+
+            @media screen {
+
+
+            .x {}
+            }
+
+
+
+            .index {}
+        "#}
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "/index.css: Can't resolve 'missing.css'"
+    );
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn bundle_import_cycle() {
+    let files = [
+        ("/a.css", "@import \"b.css\";\n.a {}\n"),
+        ("/b.css", "@import \"a.css\";\n@import \"c.css\";\n.b {}\n"),
+    ];
+    let (output, warnings) = bundle(&files, "/a.css");
+    similar_asserts::assert_eq!(output, "\n\n.b {}\n\n.a {}\n");
+    assert_eq!(
+        warnings[0].to_string(),
+        "/b.css: Circular @import of '/a.css'"
+    );
+    assert_eq!(
+        Lexer::slice_range(files[1].1, &warnings[0].range).unwrap(),
+        "@import \"a.css\";"
+    );
+    assert_eq!(warnings[1].to_string(), "/b.css: Can't load '/c.css'");
+    assert_eq!(warnings.len(), 2);
+}
+
+fn rewrite_urls(input: &str) -> String {
+    let mut rewriter = UrlRewriter::new(input, |request: &str| {
+        (!request.starts_with("data:")).then(|| {
+            match request.trim_matches(|c: char| c.is_ascii_whitespace()) {
+                "a b.png" => "/assets/a(1).png".to_string(),
+                "quote.png" => "/assets/\"quote'\n.png".to_string(),
+                request => format!("/assets/{request}"),
+            }
+        })
+    });
+    let mut output = String::new();
+    let warnings = rewriter.rewrite(&mut output).unwrap();
+    assert!(warnings.is_empty());
+    output
+}
+
+#[test]
+fn rewrite_url() {
+    let input = indoc! {r#"
+        .a {
+            background: url(a.png), URL( b.png ), url("a b.png"), url('quote.png');
+            mask: url(data:image/png;base64,iVBORw0KGgo=);
+            background-image: image-set("a.png" 1x, 'quote.png' 2x, url('b.png') 3x);
+            cursor: url(\61\ b.png), url(
+                quote.png
+            ), url("c\
+        .png");
+        }
+    "#};
+    similar_asserts::assert_eq!(
+        rewrite_urls(input),
+        indoc! {r#"
+            .a {
+                background: url(/assets/a.png), url(/assets/b.png), url("/assets/a(1).png"), url('/assets/"quote\'\a .png');
+                mask: url(data:image/png;base64,iVBORw0KGgo=);
+                background-image: image-set("/assets/a.png" 1x, '/assets/"quote\'\a .png' 2x, url('/assets/b.png') 3x);
+                cursor: url("/assets/a(1).png"), url("/assets/\"quote'\a .png"), url("/assets/c.png");
+            }
+        "#}
+    );
+}
+
+#[test]
+fn rewrite_url_import() {
+    let input = indoc! {r#"
+        @import "a.css";
+        @import url(b.css) layer(base);
+        @import /* comment */ url( 'a b.png' ) print;
+        @IMPORT url(data:text/css;base64,LmEge30=);
+        @import url("quote.png") supports(display: grid);
+        .a { background: url(a.png) }
+    "#};
+    similar_asserts::assert_eq!(
+        rewrite_urls(input),
+        indoc! {r#"
+            @import "/assets/a.css";
+            @import url(/assets/b.css) layer(base);
+            @import /* comment */ url( '/assets/a(1).png' ) print;
+            @IMPORT url(data:text/css;base64,LmEge30=);
+            @import url("/assets/\"quote'\a .png") supports(display: grid);
+            .a { background: url(/assets/a.png) }
+        "#}
+    );
+}
+
+fn transform_short_names(files: &[(&str, &str)], short_names: &mut ShortNames) -> Vec<String> {
+    for (_, input) in files {
+        short_names.reserve_globals(input, Mode::Local);
+    }
+    files
+        .iter()
+        .map(|(path, input)| {
+            let mut output = String::new();
+            let warnings = Transformer::new(input, Mode::Local, |local_name: &LocalName| {
+                short_names.name(path, local_name)
+            })
+            .transform(&mut output)
+            .unwrap();
+            assert!(warnings.is_empty(), "{}", &warnings[0]);
+            output
+        })
+        .collect()
+}
+
+#[test]
+fn short_names() {
+    let files = [
+        (
+            "/a.css",
+            indoc! {r#"
+                .title { composes: b from global; animation: title 1s; }
+                @keyframes title {}
+                :global(.a) #main { --color: red; color: var(--color); }
+            "#},
+        ),
+        (
+            "/b.css",
+            ":global(.c), :global(.-d) .title, .main { --e: 0; }\n.title { composes: main; }",
+        ),
+    ];
+    let mut short_names = ShortNames::new();
+    similar_asserts::assert_eq!(
+        transform_short_names(&files, &mut short_names),
+        vec![
+            indoc! {r#"
+                .e {  animation: e 1s; }
+                @keyframes e {}
+                .a #f { --g: red; color: var(--g); }
+            "#},
+            ".c, .-d .h, .i { --j: 0; }\n.h {  }"
+        ]
+    );
+    let mut mapping = String::new();
+    short_names.write_mapping(&mut mapping).unwrap();
+    similar_asserts::assert_eq!(
+        mapping,
+        indoc! {"
+            g\t/a.css\tcolor
+            f\t/a.css\tmain
+            e\t/a.css\ttitle
+            j\t/b.css\te
+            i\t/b.css\tmain
+            h\t/b.css\ttitle
+        "}
+    );
+
+    // `.e` becomes global, `title` of `/a.css` gets a new short name, the
+    // names that are no longer global are reused
+    let files = [
+        files[0],
+        (
+            "/b.css",
+            ":global(.e) .main { --e: 0; }\n.title { composes: main; }\n.new {}",
+        ),
+    ];
+    let mut short_names = ShortNames::from_mapping(&mapping);
+    similar_asserts::assert_eq!(
+        transform_short_names(&files, &mut short_names),
+        vec![
+            indoc! {r#"
+                .c {  animation: c 1s; }
+                @keyframes c {}
+                .a #f { --g: red; color: var(--g); }
+            "#},
+            ".e .i { --j: 0; }\n.h {  }\n.d {}"
+        ]
+    );
+}
+
+#[test]
+fn short_names_sequence() {
+    let input = (0..30).fold(String::new(), |mut input, i| {
+        write!(input, ".n{i}{{}}").unwrap();
+        input
+    });
+    let mut short_names = ShortNames::new();
+    let output = &transform_short_names(&[("/a.css", &input)], &mut short_names)[0];
+    similar_asserts::assert_eq!(
+        output,
+        ".a{}.b{}.c{}.d{}.e{}.f{}.g{}.h{}.i{}.j{}.k{}.l{}.m{}.n{}.o{}.p{}.q{}.r{}.s{}.t{}.u{}.v{}.w{}.x{}.y{}.z{}.aa{}.ba{}.ca{}.da{}"
+    );
+}
+
+#[test]
+fn short_names_keywords() {
+    let mut short_names = ShortNames::new();
+    let names: Vec<String> = (0..21000)
+        .map(|i| {
+            let name = format!("n{i}");
+            short_names.name(
+                "/a.css",
+                &LocalName {
+                    kind: LocalKind::Class,
+                    name: &name,
+                    range: Range::new(0, 0),
+                    from: None,
+                },
+            )
+        })
+        .collect();
+    for keyword in ["or", "and", "not"] {
+        assert!(!names.iter().any(|name| name == keyword), "{keyword}");
+    }
+}
+
+fn transform_used(input: &str, used_exports: &[&str]) -> String {
+    let mut output = String::new();
+    let warnings = Transformer::new(input, Mode::Local, |local_name: &LocalName| {
+        format!("_{}", local_name.name)
+    })
+    .with_used_exports(used_exports.iter().copied())
+    .transform(&mut output)
+    .unwrap();
+    assert!(warnings.is_empty(), "{}", &warnings[0]);
+    output
+}
+
+#[test]
+fn lex_rule_boundaries() {
+    let input = indoc! {r#"
+        @import "a.css";
+        /* comment */ .a, :global(.b) { color: red; }
+        @media print {
+            .c { & .d {} }
+        }
+        .e { content: "}"; }
+    "#};
+    let mut rules = Vec::new();
+    let mut lexer = Lexer::new(input);
+    let mut visitor = LexDependencies::new(
+        |dependency| {
+            if let Dependency::Rule { range } = dependency {
+                rules.push(Lexer::slice_range(input, &range).unwrap());
+            }
+        },
+        |_| {},
+        Mode::Local,
+    )
+    .with_rule_boundaries(true);
+    lexer.lex(&mut visitor);
+    assert_eq!(
+        rules,
+        vec![
+            ".a, :global(.b) { color: red; }",
+            "@media print {\n    .c { & .d {} }\n}",
+            ".e { content: \"}\"; }"
+        ]
+    );
+}
+
+#[test]
+fn transform_used_exports() {
+    let input = indoc! {r#"
+        .a { color: red; }
+        .b, .c { color: green; }
+        .d .a, .e { color: blue; }
+        .a:not(.e) { color: blue; }
+        :global(.f) .e { color: black; }
+        .used { composes: a; animation: fade 1s; list-style: dots; }
+        @keyframes fade {}
+        @keyframes unused {}
+        @counter-style dots { system: extends circles; }
+        @counter-style circles {}
+        @counter-style squares {}
+        @media print {
+            .e {}
+        }
+        div {}
+    "#};
+    similar_asserts::assert_eq!(
+        transform_used(input, &["used", "c"]),
+        indoc! {r#"
+            ._a { color: red; }
+            ._b, ._c { color: green; }
+            
+            ._a:not(._e) { color: blue; }
+            
+            ._used {  animation: _fade 1s; list-style: _dots; }
+            @keyframes _fade {}
+            
+            @counter-style _dots { system: extends _circles; }
+            @counter-style _circles {}
+            
+            @media print {
+                ._e {}
+            }
+            div {}
+        "#}
+    );
+}
+
+#[test]
+fn css_modules_scoped() {
+    let input = indoc! {r#"
+        .a, .b:hover::before {}
+        .c :deep(.d), :slotted(.e) {}
+        :global(.f) .g {
+            animation: fade 1s;
+            list-style: dots;
+        }
+        @keyframes fade {}
+        @counter-style dots {}
+    "#};
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Scoped);
+    assert!(warnings.is_empty());
+    assert_scope_insertion_dependency(input, &dependencies[0], ".a", false);
+    assert_scope_insertion_dependency(input, &dependencies[1], ".b", false);
+    assert_scope_insertion_dependency(input, &dependencies[2], ".c", false);
+    assert_replace_dependency(input, &dependencies[3], "", ":deep(");
+    assert_replace_dependency(input, &dependencies[4], "", ")");
+    assert_replace_dependency(input, &dependencies[5], "", ":slotted(");
+    assert_scope_insertion_dependency(input, &dependencies[6], ".e", true);
+    assert_replace_dependency(input, &dependencies[7], "", ")");
+    assert_replace_dependency(input, &dependencies[8], "", ":global(");
+    assert_replace_dependency(input, &dependencies[9], "", ")");
+    assert_local_keyframes_dependency(input, &dependencies[10], "fade");
+    assert_local_keyframes_decl_dependency(input, &dependencies[11], "fade");
+    assert_eq!(dependencies.len(), 12);
+}
+
+fn transform_scoped(input: &str) -> String {
+    let mut output = String::new();
+    let warnings = Transformer::new(input, Mode::Scoped, |local_name: &LocalName| {
+        format!("{}-7ba5bd90", local_name.name)
+    })
+    .with_scope_attribute("data-v-7ba5bd90")
+    .transform(&mut output)
+    .unwrap();
+    assert!(warnings.is_empty(), "{}", &warnings[0]);
+    output
+}
+
+#[test]
+fn transform_scoped_selectors() {
+    let input = indoc! {r#"
+        .a, .b .c:hover::before, div > span, [type="a b"] {}
+        .a :deep(.b), :deep(.c .d), .e:deep(.f), .g > :deep(.h) {}
+        :slotted(.a:hover), .b :slotted( .c ) {}
+        :global(.a) .b, :global .c, .d:not(.e, .f) {}
+        .a :global(.b), .a > :global(.b) .c, .d:hover + :global .e {}
+        .a\:b, :hover, .a /* comment */ {}
+        .a {
+            animation: fade 1s;
+            list-style: dots;
+            &:hover .b {}
+            @media print {
+                .c {}
+            }
+        }
+        @keyframes fade {
+            from, 50% {}
+        }
+        @keyframes :global(fade) {}
+        @counter-style dots {}
+        @media print, screen {
+            .a {}
+        }
+    "#};
+    similar_asserts::assert_eq!(
+        transform_scoped(input),
+        indoc! {r#"
+            .a[data-v-7ba5bd90], .b .c[data-v-7ba5bd90]:hover::before, div > span[data-v-7ba5bd90], [type="a b"][data-v-7ba5bd90] {}
+            .a[data-v-7ba5bd90] .b, [data-v-7ba5bd90] .c .d, .e[data-v-7ba5bd90] .f, .g > [data-v-7ba5bd90] .h {}
+            .a[data-v-7ba5bd90-s]:hover, .b .c[data-v-7ba5bd90-s] {}
+            .a .b, .c, .d[data-v-7ba5bd90]:not(.e, .f) {}
+            .a[data-v-7ba5bd90] .b, .a[data-v-7ba5bd90] > .b .c, .d[data-v-7ba5bd90]:hover + .e {}
+            .a\:b[data-v-7ba5bd90], [data-v-7ba5bd90]:hover, .a[data-v-7ba5bd90] /* comment */ {}
+            .a[data-v-7ba5bd90] {
+                animation: fade-7ba5bd90 1s;
+                list-style: dots;
+                &:hover .b[data-v-7ba5bd90] {}
+                @media print {
+                    .c[data-v-7ba5bd90] {}
+                }
+            }
+            @keyframes fade-7ba5bd90 {
+                from, 50% {}
+            }
+            @keyframes fade {}
+            @counter-style dots {}
+            @media print, screen {
+                .a[data-v-7ba5bd90] {}
+            }
+        "#}
+    );
+}
+
+#[test]
+fn scoped_composes() {
+    let input = ".a { composes: b; }";
+    let (dependencies, warnings) = collect_dependencies(input, Mode::Scoped);
+    assert_warning(input, &warnings[0], "composes");
+    assert_eq!(
+        warnings[0].to_string(),
+        "Composition is not supported in scoped mode"
+    );
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(
+        dependencies[0],
+        Dependency::ScopeInsertion { slotted: false, .. }
+    ));
+    assert_eq!(dependencies.len(), 1);
+}